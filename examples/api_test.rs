@@ -1,12 +1,13 @@
 use tracing::info;
 use walle::{
+    builtin::{on_notice, require, Permission},
     matcher, on_command, ActionCaller, ActionCallerExt, MatcherHandler, Matchers, MatchersConfig,
-    Session,
+    Rule, Session,
 };
 use walle_core::{
-    event::GroupMessageEvent,
+    event::{GroupMemberIncreaseEvent, GroupMessageEvent},
     prelude::Action,
-    segment::{Mention, MessageExt},
+    segment::{Mention, MessageExt, ToMsgSegment},
     value_map,
 };
 
@@ -15,7 +16,8 @@ async fn main() {
     let matchers = Matchers::default()
         .add_matcher(recall_test_plugin().boxed())
         .add_matcher(mute_test().boxed())
-        .add_matcher(unmute_test().boxed());
+        .add_matcher(unmute_test().boxed())
+        .add_matcher(welcome_test().boxed());
     // .add_matcher(member_test())
     // .add_matcher(forward_test_plugin());
     let walle = walle::new_walle(matchers, "debug");
@@ -69,7 +71,7 @@ fn recall_test_plugin() -> impl MatcherHandler {
 
 fn mute_test() -> impl MatcherHandler {
     on_command!(Mute, "./mute");
-    matcher(
+    require(Permission::Admin).layer(matcher(
         |Mute(segs): Mute, event: GroupMessageEvent, s: Session| async move {
             for seg in segs.extract::<Mention>() {
                 s.call_action(Action {
@@ -87,12 +89,12 @@ fn mute_test() -> impl MatcherHandler {
                 .unwrap();
             }
         },
-    )
+    ))
 }
 
 fn unmute_test() -> impl MatcherHandler {
     on_command!(Unmute, "./unmute");
-    matcher(
+    require(Permission::Admin).layer(matcher(
         |Unmute(segs): Unmute, event: GroupMessageEvent, s: Session| async move {
             for seg in segs.extract::<Mention>() {
                 s.call_action(Action {
@@ -107,7 +109,23 @@ fn unmute_test() -> impl MatcherHandler {
                 .unwrap();
             }
         },
-    )
+    ))
+}
+
+fn welcome_test() -> impl MatcherHandler {
+    on_notice("group_member_increase").layer(matcher(
+        |event: GroupMemberIncreaseEvent, s: Session| async move {
+            s.reply(vec![
+                Mention {
+                    user_id: event.detail_type.user_id,
+                }
+                .to_segment(),
+                " welcome!".into(),
+            ])
+            .await
+            .ok();
+        },
+    ))
 }
 
 // fn member_test() -> Matcher {
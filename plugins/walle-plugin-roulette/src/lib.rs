@@ -6,14 +6,20 @@ use tokio::sync::Mutex;
 use walle::builtin::{mention_user, start_with, trim};
 use walle::walle_core::event::GroupMessageEvent;
 use walle::walle_core::util::ValueMapExt;
-use walle::walle_core::WalleResult;
-use walle::{matcher, on_command, MatcherHandler, Session};
+use walle::walle_core::{prelude::async_trait, WalleResult};
+use walle::{on_command, plugin, Matcher, MatcherHandler, Plugin, Session, Signal};
 
-on_command!(Roulette, Start => "轮盘赌", Shot => "shot");
+on_command!(Roulette, Start => ["轮盘赌", "roulette"], Shot => "shot");
 
-pub struct RouletteMatcher(Mutex<HashMap<String, Vec<(String, String, u8, u8, u8)>>>);
+/// (challenger, acceptor, count, all, shot)
+type Game = (String, String, u8, u8, u8);
 
+#[derive(Default)]
+pub struct RouletteMatcher(Mutex<HashMap<String, Vec<Game>>>);
+
+#[plugin]
 impl RouletteMatcher {
+    #[handler]
     pub async fn roalette(
         &self,
         ro: Roulette,
@@ -56,7 +62,7 @@ impl RouletteMatcher {
                 let mut locked = self.0.lock().await;
                 if let Some(v) = locked.get_mut(&event.detail_type.group_id) {
                     let mut need_remove = None;
-                    for (index, (a, b, count, all, shot)) in v.into_iter().enumerate() {
+                    for (index, (a, b, count, all, shot)) in v.iter_mut().enumerate() {
                         if a == event.ty.user_id.as_str() || b == event.ty.user_id.as_str() {
                             if count == shot {
                                 s.reply("嘣！正中靶心！").await?;
@@ -71,7 +77,6 @@ impl RouletteMatcher {
                     if let Some(index) = need_remove {
                         v.remove(index);
                         if v.is_empty() {
-                            drop(v);
                             locked.remove(&event.detail_type.group_id);
                         }
                     }
@@ -82,18 +87,48 @@ impl RouletteMatcher {
     }
 }
 
-matcher!(
-    failable: RouletteMatcher,
-    roalette,
-    ro: Roulette,
-    event: GroupMessageEvent
-);
+/// `RouletteMatcher` 中的所有 handler，可通过 `add_matcher` 注册
+pub struct RouletteHandler(Vec<Matcher>);
+
+#[async_trait]
+impl MatcherHandler for RouletteHandler {
+    async fn handle(&self, session: Session) -> Signal {
+        let mut signal = Signal::NotMatch;
+        for matcher in &self.0 {
+            signal = signal | matcher.handle(session.clone()).await;
+        }
+        signal
+    }
+    fn commands(&self) -> Vec<String> {
+        self.0.iter().flat_map(|m| m.commands()).collect()
+    }
+}
 
+/// 轮盘赌 matcher
 pub fn roulette() -> impl MatcherHandler {
-    Arc::new(RouletteMatcher(Mutex::default()))
+    RouletteHandler(Arc::new(roulette_plugin()).matchers())
+}
+
+/// 轮盘赌插件，通过 `Matchers::add_plugin` 注册
+pub fn roulette_plugin() -> RouletteMatcher {
+    RouletteMatcher::default()
+}
+
+#[test]
+fn register() {
+    let matchers = walle::Matchers::default()
+        .add_matcher(roulette().boxed())
+        .add_plugin(roulette_plugin());
+    assert_eq!(matchers.inner.len(), 2);
+    for matcher in &matchers.inner {
+        let mut commands = matcher.commands();
+        commands.sort();
+        assert_eq!(commands, ["roulette", "shot", "轮盘赌"]);
+    }
 }
 
 #[tokio::test]
+#[ignore = "starts a real walle instance and never returns"]
 async fn t() {
     let matchers = walle::Matchers::default().add_matcher(roulette().boxed());
     let walle = walle::new_walle(matchers, "debug");
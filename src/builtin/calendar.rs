@@ -0,0 +1,273 @@
+use std::str::FromStr;
+
+use time::OffsetDateTime;
+pub use time::{Time, UtcOffset, Weekday};
+use walle_core::{prelude::async_trait, WalleError, WalleResult};
+
+use crate::{Rule, Session, Signal};
+
+#[derive(Debug, Clone)]
+enum TimeCondition {
+    /// start <= t < end，start 晚于 end 时跨越午夜
+    Between(Time, Time),
+    Weekdays(Vec<Weekday>),
+    Cron(CronExpr),
+}
+
+impl TimeCondition {
+    fn matches(&self, now: &OffsetDateTime) -> bool {
+        match self {
+            Self::Between(start, end) => {
+                let t = now.time();
+                if start <= end {
+                    *start <= t && t < *end
+                } else {
+                    *start <= t || t < *end
+                }
+            }
+            Self::Weekdays(days) => days.contains(&now.weekday()),
+            Self::Cron(cron) => cron.matches(now),
+        }
+    }
+}
+
+/// 时间段 Rule
+///
+/// 所有条件同时满足时匹配，未指定时区时使用 `MatchersConfig::utc_offset`。
+/// 构造时传入的时间或 cron 表达式无效时会 panic。
+#[derive(Debug, Clone, Default)]
+pub struct TimeRule {
+    conditions: Vec<TimeCondition>,
+    pub offset: Option<UtcOffset>,
+    /// 反转匹配结果，用于免打扰时段等场景
+    pub invert: bool,
+}
+
+/// 解析 `HH:MM` 或 `HH:MM:SS` 格式的时间
+pub fn parse_time(s: &str) -> WalleResult<Time> {
+    let mut parts = s.trim().splitn(3, ':').map(str::parse::<u8>);
+    let mut next = |required: bool| match parts.next() {
+        Some(Ok(v)) => Ok(v),
+        None if !required => Ok(0),
+        _ => Err(WalleError::Other(format!("invalid time: {}", s))),
+    };
+    let (hour, minute, second) = (next(true)?, next(true)?, next(false)?);
+    Time::from_hms(hour, minute, second).map_err(|e| WalleError::Other(e.to_string()))
+}
+
+impl TimeRule {
+    /// 限制在 `start` 至 `end` 之间，如 `"09:00"`、`"23:30:00"`
+    pub fn between(mut self, start: &str, end: &str) -> Self {
+        let start = parse_time(start).unwrap();
+        let end = parse_time(end).unwrap();
+        self.conditions.push(TimeCondition::Between(start, end));
+        self
+    }
+
+    /// 限制在指定星期
+    pub fn days<I: IntoIterator<Item = Weekday>>(mut self, days: I) -> Self {
+        self.conditions
+            .push(TimeCondition::Weekdays(days.into_iter().collect()));
+        self
+    }
+
+    /// 限制在周一至周五
+    pub fn weekdays_only(self) -> Self {
+        self.days([
+            Weekday::Monday,
+            Weekday::Tuesday,
+            Weekday::Wednesday,
+            Weekday::Thursday,
+            Weekday::Friday,
+        ])
+    }
+
+    /// 限制在 cron 表达式匹配的分钟内，见 `CronExpr`
+    pub fn cron(mut self, expr: &str) -> Self {
+        self.conditions
+            .push(TimeCondition::Cron(expr.parse().unwrap()));
+        self
+    }
+
+    /// 使用指定时区
+    pub fn offset(self, offset: UtcOffset) -> Self {
+        Self {
+            offset: Some(offset),
+            ..self
+        }
+    }
+
+    /// 反转匹配结果
+    pub fn invert(self) -> Self {
+        Self {
+            invert: !self.invert,
+            ..self
+        }
+    }
+
+    /// `now` 是否满足所有条件（已考虑 `invert`，不考虑 `offset`）
+    pub fn matches_at(&self, now: &OffsetDateTime) -> bool {
+        self.conditions.iter().all(|c| c.matches(now)) != self.invert
+    }
+}
+
+#[async_trait]
+impl Rule for TimeRule {
+    async fn rule(&self, session: &Session) -> Signal {
+        let offset = self.offset.unwrap_or(session.config.utc_offset);
+        if self.matches_at(&OffsetDateTime::now_utc().to_offset(offset)) {
+            Signal::Matched
+        } else {
+            Signal::NotMatch
+        }
+    }
+}
+
+/// 仅在 `start` 至 `end` 之间匹配，`start` 晚于 `end` 时跨越午夜
+pub fn active_between(start: &str, end: &str) -> TimeRule {
+    TimeRule::default().between(start, end)
+}
+
+/// 仅在 `start` 至 `end` 之外匹配
+pub fn inactive_between(start: &str, end: &str) -> TimeRule {
+    active_between(start, end).invert()
+}
+
+/// 仅在指定星期匹配
+pub fn on_days<I: IntoIterator<Item = Weekday>>(days: I) -> TimeRule {
+    TimeRule::default().days(days)
+}
+
+/// 仅在周一至周五匹配
+pub fn weekdays_only() -> TimeRule {
+    TimeRule::default().weekdays_only()
+}
+
+/// 仅在 cron 表达式匹配的分钟内匹配
+pub fn active_cron(expr: &str) -> TimeRule {
+    TimeRule::default().cron(expr)
+}
+
+/// 分钟精度的 cron 表达式：`分 时 日 月 星期`
+///
+/// 每个字段支持 `*`、`5`、`1-5`、`*/15`、`9-17/2` 及以逗号分隔的组合，
+/// 星期可使用 0-7（0 与 7 均为周日）或 `sun`-`sat`。
+/// 日与星期均被限制时满足其一即可。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_any: bool,
+    weekdays_any: bool,
+}
+
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+fn parse_value(s: &str, names: &[&str]) -> Option<u8> {
+    s.parse().ok().or_else(|| {
+        names
+            .iter()
+            .position(|n| n.eq_ignore_ascii_case(s))
+            .map(|i| i as u8)
+    })
+}
+
+fn parse_field(field: &str, min: u8, max: u8, names: &[&str]) -> WalleResult<u64> {
+    let err = || WalleError::Other(format!("invalid cron field: {}", field));
+    let mut bits = 0u64;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u8>().map_err(|_| err())?),
+            None => (item, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (
+                parse_value(start, names).ok_or_else(err)?,
+                parse_value(end, names).ok_or_else(err)?,
+            )
+        } else {
+            let v = parse_value(range, names).ok_or_else(err)?;
+            (v, if item.contains('/') { max } else { v })
+        };
+        if step == 0 || start < min || end > max || start > end {
+            return Err(err());
+        }
+        for v in (start..=end).step_by(step as usize) {
+            bits |= 1 << v;
+        }
+    }
+    Ok(bits)
+}
+
+impl FromStr for CronExpr {
+    type Err = WalleError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(WalleError::Other(format!(
+                "cron expression requires 5 fields: {}",
+                s
+            )));
+        };
+        let mut weekday_bits = parse_field(weekdays, 0, 7, &WEEKDAY_NAMES)?;
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits |= 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minutes, 0, 59, &[])?,
+            hours: parse_field(hours, 0, 23, &[])?,
+            days: parse_field(days, 1, 31, &[])?,
+            months: parse_field(months, 1, 12, &[])?,
+            weekdays: weekday_bits,
+            days_any: days == "*",
+            weekdays_any: weekdays == "*",
+        })
+    }
+}
+
+impl CronExpr {
+    pub fn matches(&self, t: &OffsetDateTime) -> bool {
+        let has = |bits: u64, v: u8| bits & (1 << v) != 0;
+        let day = has(self.days, t.day());
+        let weekday = has(self.weekdays, t.weekday().number_days_from_sunday());
+        let day_matched = match (self.days_any, self.weekdays_any) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        has(self.minutes, t.minute())
+            && has(self.hours, t.hour())
+            && has(self.months, t.month() as u8)
+            && day_matched
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{active_between, CronExpr};
+    use time::macros::datetime;
+
+    #[test]
+    fn time_rules() {
+        // 2022-10-17 is a Monday
+        let monday_noon = datetime!(2022-10-17 12:00 +8);
+        let sunday_night = datetime!(2022-10-23 23:30 +8);
+        let night = active_between("22:00", "07:00");
+        assert!(!night.matches_at(&monday_noon));
+        assert!(night.matches_at(&sunday_night));
+        let work = active_between("09:00", "18:00").weekdays_only().invert();
+        assert!(!work.matches_at(&monday_noon));
+        assert!(work.matches_at(&sunday_night));
+
+        let cron: CronExpr = "*/30 9-17 * * mon-fri".parse().unwrap();
+        assert!(cron.matches(&monday_noon));
+        assert!(!cron.matches(&sunday_night));
+        let cron: CronExpr = "30 23 1 * 0".parse().unwrap();
+        assert!(cron.matches(&sunday_night));
+        assert!("60 * * * *".parse::<CronExpr>().is_err());
+    }
+}
@@ -0,0 +1,655 @@
+use std::{collections::VecDeque, str::FromStr};
+
+use walle_core::{
+    event::Event,
+    segment::{MsgSegment, Segments},
+    util::{Value, ValueMapExt},
+    WalleError, WalleResult,
+};
+
+use crate::{MatchersConfig, Session};
+
+pub use walle_macros::Command;
+
+/// 可由 `#[derive(Command)]` 生成的命令参数解析
+///
+/// ```ignore
+/// #[derive(Command)]
+/// #[command(name = "roll")]
+/// struct Roll {
+///     /// 骰子面数
+///     #[arg(default = "6")]
+///     sides: u32,
+///     /// 显示每次结果
+///     #[arg(long, short = 'v')]
+///     verbose: bool,
+///     #[arg(long, default = "1")]
+///     times: u32,
+/// }
+/// ```
+///
+/// 字段类型决定参数形式：`Option<T>` 为可选参数，`Vec<T>` 收集剩余参数，
+/// 带有 `long` 或 `short` 的 `bool` 为开关；其余类型通过 `FromStr` 解析，
+/// mention 消息段作为其 user_id 参与解析。
+/// enum 的每个变体为一个子命令，单字段元组变体会委托给字段类型的 `Command` 实现。
+pub trait Command: Sized {
+    const SPEC: CommandSpec;
+    /// 从命令名之后的参数解析
+    fn parse_args(args: &mut CommandArgs) -> WalleResult<Self>;
+    fn usage() -> String {
+        Self::SPEC.usage()
+    }
+}
+
+/// 命令参数形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    /// 按顺序出现的参数
+    Positional,
+    /// 收集剩余的所有位置参数
+    Rest,
+    /// `--name` 开关
+    Flag,
+    /// `--name value` 或 `--name=value` 选项
+    Named,
+}
+
+/// 单个参数描述
+#[derive(Debug, Clone, Copy)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub kind: ArgKind,
+    pub short: Option<char>,
+    pub required: bool,
+    pub default: Option<&'static str>,
+}
+
+/// 命令描述，用于解析与生成用法说明
+#[derive(Debug, Clone, Copy)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub about: &'static str,
+    pub args: &'static [ArgSpec],
+    pub subcommands: &'static [CommandSpec],
+}
+
+/// 命令参数
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgToken {
+    Text(String),
+    /// mention 消息段的 user_id
+    Mention(String),
+    /// 其他消息段
+    Segment(MsgSegment),
+}
+
+impl ArgToken {
+    /// 文本或 mention 的 user_id
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Text(s) | Self::Mention(s) => Some(s),
+            Self::Segment(_) => None,
+        }
+    }
+
+    fn option(&self) -> Option<&str> {
+        match self {
+            Self::Text(s) if s.len() > 1 && s.starts_with('-') => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// 以空白分隔、支持引号的命令参数序列
+///
+/// reply 消息段会被忽略。
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandArgs {
+    tokens: VecDeque<ArgToken>,
+}
+
+fn split_text(text: &str, tokens: &mut VecDeque<ArgToken>) -> WalleResult<()> {
+    let mut chars = text.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            return Ok(());
+        };
+        let mut token = String::new();
+        if first == '"' || first == '\'' {
+            chars.next();
+            loop {
+                match chars.next() {
+                    Some(c) if c == first => break,
+                    Some('\\') if first == '"' => token.extend(chars.next()),
+                    Some(c) => token.push(c),
+                    None => return Err(WalleError::Other(format!("unclosed quote in: {}", text))),
+                }
+            }
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                token.push(c);
+            }
+        }
+        tokens.push_back(ArgToken::Text(token));
+    }
+}
+
+impl CommandArgs {
+    pub fn new(segments: Segments) -> WalleResult<Self> {
+        let mut tokens = VecDeque::new();
+        for seg in segments {
+            match seg.ty.as_str() {
+                "text" => split_text(seg.data.try_get_as_ref::<&str>("text")?, &mut tokens)?,
+                "mention" => tokens.push_back(ArgToken::Mention(seg.data.get_downcast("user_id")?)),
+                "reply" => {}
+                _ => tokens.push_back(ArgToken::Segment(seg)),
+            }
+        }
+        Ok(Self { tokens })
+    }
+
+    /// 解析 session 中的消息，不会修改 event
+    pub fn from_session(session: &Session) -> WalleResult<Self> {
+        let segments = session
+            .event
+            .extra
+            .try_get_as_ref::<&Vec<Value>>("message")?
+            .iter()
+            .cloned()
+            .map(MsgSegment::try_from)
+            .collect::<WalleResult<Segments>>()?;
+        Self::new(segments)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn peek(&self) -> Option<&ArgToken> {
+        self.tokens.front()
+    }
+
+    /// 第一个参数为 `starts` 中任一前缀加上 `names` 之一时将其移除并返回 true
+    ///
+    /// 命令名之后以 `seps` 分隔的部分将作为新的参数，用于子命令。
+    pub fn strip_command(&mut self, starts: &[String], seps: &[String], names: &[&str]) -> bool {
+        let Some(ArgToken::Text(first)) = self.tokens.front() else {
+            return false;
+        };
+        let mut starts: Vec<&str> = starts.iter().map(String::as_str).collect();
+        starts.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        for start in starts {
+            let Some(rest) = first.strip_prefix(start) else {
+                continue;
+            };
+            let words = split_sep(rest, seps);
+            if words.first().is_some_and(|word| names.contains(word)) {
+                let words: Vec<String> = words[1..].iter().map(|w| w.to_string()).collect();
+                self.tokens.pop_front();
+                for word in words.into_iter().rev() {
+                    self.tokens.push_front(ArgToken::Text(word));
+                }
+                return true;
+            }
+        }
+        false
+    }
+}
+
+fn split_sep<'a>(s: &'a str, seps: &[String]) -> Vec<&'a str> {
+    let mut words = vec![];
+    let mut rest = s;
+    while let Some((index, len)) = seps
+        .iter()
+        .filter(|sep| !sep.is_empty())
+        .filter_map(|sep| rest.find(sep.as_str()).map(|i| (i, sep.len())))
+        .min()
+    {
+        words.push(&rest[..index]);
+        rest = &rest[index + len..];
+    }
+    words.push(rest);
+    words.retain(|w| !w.is_empty());
+    words
+}
+
+/// 命令名及其别名，用于 `on_command!`
+pub trait CommandNames {
+    fn command_names(&self) -> Vec<&str>;
+}
+
+impl CommandNames for &str {
+    fn command_names(&self) -> Vec<&str> {
+        vec![self]
+    }
+}
+
+impl CommandNames for String {
+    fn command_names(&self) -> Vec<&str> {
+        vec![self]
+    }
+}
+
+impl<const N: usize> CommandNames for [&str; N] {
+    fn command_names(&self) -> Vec<&str> {
+        self.to_vec()
+    }
+}
+
+impl CommandNames for &[&str] {
+    fn command_names(&self) -> Vec<&str> {
+        self.to_vec()
+    }
+}
+
+/// 去除 `text` 开头的命令前缀与 `names` 中任一命令名，返回剩余文本
+///
+/// 命令前缀见 `MatchersConfig::command_start`，较长的前缀与命令名优先。
+/// 命令名之后须为文本结尾（如其后为非文本消息段）、`command_sep`，
+/// 或 ASCII 字母、数字与 `_` 以外的字符，因此 `shotgun` 不会匹配命令 `shot`，
+/// 而 `查询北京`、`禁言@张三` 等不加空格的中文参数可以匹配命令 `查询`、`禁言`。
+pub fn strip_command_prefix<'a>(
+    config: &MatchersConfig,
+    event: &Event,
+    text: &'a str,
+    names: &[&str],
+) -> Option<&'a str> {
+    let mut starts: Vec<&str> = config
+        .command_start_for(event)
+        .iter()
+        .map(String::as_str)
+        .collect();
+    starts.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    let mut names = names.to_vec();
+    names.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    let boundary = |rest: &str| {
+        rest.chars()
+            .next()
+            .is_none_or(|c| !c.is_ascii_alphanumeric() && c != '_')
+            || config
+                .command_sep
+                .iter()
+                .any(|sep| !sep.is_empty() && rest.starts_with(sep.as_str()))
+    };
+    starts.iter().find_map(|start| {
+        let rest = text.strip_prefix(start)?;
+        names
+            .iter()
+            .filter_map(|name| rest.strip_prefix(name))
+            .find(|rest| boundary(rest))
+    })
+}
+
+/// 解析 session 消息中以 `C` 命令名或别名开头的命令
+pub fn parse_command<C: Command>(session: &Session) -> WalleResult<C> {
+    let mut args = CommandArgs::from_session(session)?;
+    let config = &session.config;
+    let names = C::SPEC.names();
+    if !args.strip_command(
+        config.command_start_for(&session.event),
+        &config.command_sep,
+        &names,
+    ) {
+        return Err(WalleError::Other(format!(
+            "Command not match with {}",
+            names.join(" or ")
+        )));
+    }
+    session.claim_command(C::usage());
+    C::parse_args(&mut args)
+}
+
+/// 仅有命令名与别名的用法说明，用于 `on_command!`
+pub fn command_usage(names: &[&str]) -> String {
+    let Some((name, aliases)) = names.split_first() else {
+        return String::default();
+    };
+    let mut usage = format!("Usage: {}", name);
+    if !aliases.is_empty() {
+        usage.push_str(&format!("\nAliases: {}", aliases.join(", ")));
+    }
+    usage
+}
+
+impl Iterator for CommandArgs {
+    type Item = ArgToken;
+    fn next(&mut self) -> Option<ArgToken> {
+        self.tokens.pop_front()
+    }
+}
+
+/// 已按 `CommandSpec` 分组的参数
+#[derive(Debug)]
+pub struct ParsedArgs {
+    spec: CommandSpec,
+    values: Vec<Vec<ArgToken>>,
+}
+
+impl ParsedArgs {
+    fn take<T: FromStr>(&mut self, index: usize) -> WalleResult<Vec<T>> {
+        let arg = &self.spec.args[index];
+        let mut values = std::mem::take(&mut self.values[index]);
+        if values.is_empty() {
+            if let Some(default) = arg.default {
+                values.push(ArgToken::Text(default.to_owned()));
+            }
+        }
+        values
+            .into_iter()
+            .map(|token| {
+                token.as_str().and_then(|s| s.parse().ok()).ok_or_else(|| {
+                    self.spec
+                        .error(&format!("invalid value for <{}>", arg.name))
+                })
+            })
+            .collect()
+    }
+
+    /// 必需参数或带有默认值的参数
+    pub fn get<T: FromStr>(&mut self, index: usize) -> WalleResult<T> {
+        self.take(index)?.into_iter().next().ok_or_else(|| {
+            self.spec.error(&format!(
+                "missing argument <{}>",
+                self.spec.args[index].name
+            ))
+        })
+    }
+
+    pub fn get_optional<T: FromStr>(&mut self, index: usize) -> WalleResult<Option<T>> {
+        Ok(self.take(index)?.into_iter().next())
+    }
+
+    pub fn get_all<T: FromStr>(&mut self, index: usize) -> WalleResult<Vec<T>> {
+        self.take(index)
+    }
+
+    pub fn flag(&self, index: usize) -> bool {
+        !self.values[index].is_empty()
+    }
+}
+
+impl CommandSpec {
+    /// 命令名与别名
+    pub fn names(&self) -> Vec<&'static str> {
+        std::iter::once(self.name)
+            .chain(self.aliases.iter().copied())
+            .collect()
+    }
+
+    /// 附带用法说明的解析错误
+    pub fn error(&self, msg: &str) -> WalleError {
+        WalleError::Other(format!("{}\n{}", msg, self.usage()))
+    }
+
+    fn find_option(&self, option: &str) -> Option<usize> {
+        self.args.iter().position(|arg| {
+            matches!(arg.kind, ArgKind::Flag | ArgKind::Named)
+                && match option.strip_prefix("--") {
+                    Some(long) => long == arg.name,
+                    None => option.chars().nth(1) == arg.short && option.chars().count() == 2,
+                }
+        })
+    }
+
+    /// 将 `args` 中的所有参数按描述分组
+    pub fn parse(&self, args: &mut CommandArgs) -> WalleResult<ParsedArgs> {
+        let mut values = vec![Vec::new(); self.args.len()];
+        let mut positionals = self
+            .args
+            .iter()
+            .enumerate()
+            .filter(|(_, arg)| matches!(arg.kind, ArgKind::Positional | ArgKind::Rest))
+            .map(|(index, _)| index)
+            .peekable();
+        let mut options_end = false;
+        while let Some(token) = args.next() {
+            if let Some(option) = token.option().filter(|_| !options_end) {
+                if option == "--" {
+                    options_end = true;
+                    continue;
+                }
+                let (option, value) = match option.split_once('=') {
+                    Some((option, value)) => (option, Some(value)),
+                    None => (option, None),
+                };
+                if let Some(index) = self.find_option(option) {
+                    let value = match (self.args[index].kind, value) {
+                        (ArgKind::Flag, None) => token.clone(),
+                        (ArgKind::Flag, Some(_)) => {
+                            return Err(self.error(&format!("{} takes no value", option)))
+                        }
+                        (_, Some(value)) => ArgToken::Text(value.to_owned()),
+                        (_, None) => args
+                            .next()
+                            .ok_or_else(|| self.error(&format!("missing value for {}", option)))?,
+                    };
+                    values[index].push(value);
+                    continue;
+                }
+                if option.starts_with("--") {
+                    return Err(self.error(&format!("unknown option {}", option)));
+                }
+                // 不是已知的短选项时作为位置参数，如负数
+            }
+            let Some(&index) = positionals.peek() else {
+                return Err(self.error(&format!(
+                    "unexpected argument {}",
+                    token.as_str().unwrap_or("<segment>")
+                )));
+            };
+            values[index].push(token);
+            if self.args[index].kind == ArgKind::Positional {
+                positionals.next();
+            }
+        }
+        for (arg, values) in self.args.iter().zip(&values) {
+            if arg.required && arg.default.is_none() && values.is_empty() {
+                return Err(self.error(&match arg.kind {
+                    ArgKind::Named => format!("missing option --{}", arg.name),
+                    _ => format!("missing argument <{}>", arg.name),
+                }));
+            }
+        }
+        Ok(ParsedArgs {
+            spec: *self,
+            values,
+        })
+    }
+
+    /// 移除并返回子命令序号
+    pub fn subcommand(&self, args: &mut CommandArgs) -> WalleResult<usize> {
+        let name = args.peek().and_then(ArgToken::as_str);
+        match self
+            .subcommands
+            .iter()
+            .position(|sub| name.is_some_and(|name| sub.names().contains(&name)))
+        {
+            Some(index) => {
+                args.next();
+                Ok(index)
+            }
+            None => Err(self.error(&match name {
+                Some(name) => format!("unknown subcommand {}", name),
+                None => "missing subcommand".to_owned(),
+            })),
+        }
+    }
+
+    /// 用法说明
+    pub fn usage(&self) -> String {
+        let mut line = format!("Usage: {}", self.name);
+        for arg in self.args {
+            let short = arg.short.map(|c| format!("-{}|", c)).unwrap_or_default();
+            let s = match arg.kind {
+                ArgKind::Positional if arg.required && arg.default.is_none() => {
+                    format!("<{}>", arg.name)
+                }
+                ArgKind::Positional => format!("[{}]", arg.name),
+                ArgKind::Rest => format!("[{}...]", arg.name),
+                ArgKind::Flag => format!("[{}--{}]", short, arg.name),
+                ArgKind::Named if arg.required && arg.default.is_none() => {
+                    format!("{}--{} <{}>", short, arg.name, arg.name)
+                }
+                ArgKind::Named => format!("[{}--{} <{}>]", short, arg.name, arg.name),
+            };
+            line.push(' ');
+            line.push_str(&s);
+        }
+        if !self.subcommands.is_empty() {
+            line.push_str(" <subcommand>");
+        }
+        let mut lines = vec![line];
+        if !self.aliases.is_empty() {
+            lines.push(format!("Aliases: {}", self.aliases.join(", ")));
+        }
+        if !self.about.is_empty() {
+            lines.push(self.about.to_owned());
+        }
+        for arg in self.args {
+            if arg.help.is_empty() && arg.default.is_none() {
+                continue;
+            }
+            let name = match arg.kind {
+                ArgKind::Flag | ArgKind::Named => format!("--{}", arg.name),
+                _ => arg.name.to_owned(),
+            };
+            let default = arg
+                .default
+                .map(|d| format!(" (default: {})", d))
+                .unwrap_or_default();
+            lines.push(format!("  {}  {}{}", name, arg.help, default));
+        }
+        for sub in self.subcommands {
+            lines.push(format!("  {} {}  {}", self.name, sub.name, sub.about));
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Command, CommandArgs};
+    use walle_core::segment::{IntoMessage, MsgSegment, Segments};
+
+    /// 掷骰子
+    #[derive(Command, Debug, PartialEq)]
+    #[command(alias = "掷骰", crate = "crate")]
+    struct Roll {
+        /// 骰子面数
+        #[arg(default = "6")]
+        sides: u32,
+        label: Option<String>,
+        #[arg(long, short = 'v')]
+        verbose: bool,
+        #[arg(long, default = "1")]
+        times: u32,
+    }
+
+    #[derive(Command, Debug, PartialEq)]
+    #[command(name = "admin", crate = "crate")]
+    enum Admin {
+        /// 禁言
+        Ban {
+            user: String,
+            #[arg(default = "60")]
+            minutes: u32,
+        },
+        List,
+        Roll(Roll),
+    }
+
+    fn args(segs: Segments) -> CommandArgs {
+        CommandArgs::new(segs).unwrap()
+    }
+
+    fn text(s: &str) -> Segments {
+        s.to_owned().into_message()
+    }
+
+    #[test]
+    fn derive_command() {
+        let mut a = args(text("20 \"big roll\" -v --times=3"));
+        assert_eq!(
+            Roll::parse_args(&mut a).unwrap(),
+            Roll {
+                sides: 20,
+                label: Some("big roll".to_owned()),
+                verbose: true,
+                times: 3,
+            }
+        );
+        let mut a = args(text(""));
+        assert_eq!(Roll::parse_args(&mut a).unwrap().sides, 6);
+        let err = Roll::parse_args(&mut args(text("x"))).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Usage: roll [sides] [label] [-v|--verbose]"));
+
+        let mut segs = text("ban ");
+        segs.push(MsgSegment {
+            ty: "mention".to_owned(),
+            data: walle_core::value_map! { "user_id": "123" },
+        });
+        assert_eq!(
+            Admin::parse_args(&mut args(segs)).unwrap(),
+            Admin::Ban {
+                user: "123".to_owned(),
+                minutes: 60
+            }
+        );
+        assert_eq!(
+            Admin::parse_args(&mut args(text("roll 12"))).unwrap(),
+            Admin::Roll(Roll {
+                sides: 12,
+                label: None,
+                verbose: false,
+                times: 1,
+            })
+        );
+        assert!(Admin::parse_args(&mut args(text("list extra"))).is_err());
+        assert!(Admin::usage().contains("admin ban  禁言"));
+    }
+
+    #[test]
+    fn command_prefix() {
+        let starts = vec!["/".to_owned(), String::default()];
+        let seps = vec![".".to_owned()];
+        let mut a = args(text("/admin.ban 1"));
+        assert!(a.strip_command(&starts, &seps, &Admin::SPEC.names()));
+        assert_eq!(
+            Admin::parse_args(&mut a).unwrap(),
+            Admin::Ban {
+                user: "1".to_owned(),
+                minutes: 60
+            }
+        );
+        let mut a = args(text("掷骰 3"));
+        assert!(a.strip_command(&starts, &seps, &Roll::SPEC.names()));
+        assert_eq!(Roll::parse_args(&mut a).unwrap().sides, 3);
+        assert!(!args(text("!roll")).strip_command(&starts, &seps, &Roll::SPEC.names()));
+
+        let config = crate::MatchersConfig::default();
+        let event = crate::matcher::mock::group_message("user", "");
+        let strip = |text| super::strip_command_prefix(&config, &event, text, &["shot"]);
+        assert_eq!(strip("shot"), Some(""));
+        assert_eq!(strip("shot 1"), Some(" 1"));
+        assert_eq!(strip("shotgun"), None);
+        assert_eq!(strip("shot_1"), None);
+        assert_eq!(strip("shot北京"), Some("北京"));
+        let strip = |text| super::strip_command_prefix(&config, &event, text, &["查询", "禁言"]);
+        assert_eq!(strip("查询北京"), Some("北京"));
+        assert_eq!(strip("禁言@张三"), Some("@张三"));
+        assert_eq!(strip("禁言 张三"), Some(" 张三"));
+        assert_eq!(
+            super::command_usage(&["roulette", "轮盘赌"]),
+            "Usage: roulette\nAliases: 轮盘赌"
+        );
+        assert_eq!(
+            <Roll as crate::FromSessionPart>::commands(),
+            vec!["roll", "掷骰"]
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use std::marker::PhantomData;
+
+use walle_core::{
+    event::{
+        BaseEvent, DetailTypeLevel, Event, ImplLevel, ParseEvent, PlatformLevel, SubTypeLevel,
+        TryFromEvent, TypeLevel,
+    },
+    prelude::{async_trait, WalleError},
+};
+
+use crate::{Rule, Session, Signal};
+
+/// 事件类型 Rule，仅检查字段，不消耗 event
+///
+/// 为 None 的字段不参与检查
+#[derive(Debug, Clone, Default)]
+pub struct EventTypeRule {
+    pub ty: Option<String>,
+    pub detail_type: Option<String>,
+    pub sub_type: Option<String>,
+    pub platform: Option<String>,
+    pub implt: Option<String>,
+}
+
+impl EventTypeRule {
+    pub fn detail_type<S: ToString>(self, detail_type: S) -> Self {
+        Self {
+            detail_type: Some(detail_type.to_string()),
+            ..self
+        }
+    }
+    pub fn sub_type<S: ToString>(self, sub_type: S) -> Self {
+        Self {
+            sub_type: Some(sub_type.to_string()),
+            ..self
+        }
+    }
+    pub fn platform<S: ToString>(self, platform: S) -> Self {
+        Self {
+            platform: Some(platform.to_string()),
+            ..self
+        }
+    }
+    pub fn implt<S: ToString>(self, implt: S) -> Self {
+        Self {
+            implt: Some(implt.to_string()),
+            ..self
+        }
+    }
+}
+
+fn check(expect: &Option<String>, found: &str) -> bool {
+    expect.as_ref().is_none_or(|expect| expect == found)
+}
+
+#[async_trait]
+impl Rule for EventTypeRule {
+    async fn rule(&self, session: &Session) -> Signal {
+        let event = &session.event;
+        if !(check(&self.ty, &event.ty)
+            && check(&self.detail_type, &event.detail_type)
+            && check(&self.sub_type, &event.sub_type))
+        {
+            return Signal::NotMatch;
+        }
+        if self.platform.is_some() || self.implt.is_some() {
+            let Some(selft) = &session.selft else {
+                return Signal::NotMatch;
+            };
+            if !check(&self.platform, &selft.platform) {
+                return Signal::NotMatch;
+            }
+            if self.implt.is_some() && !check(&self.implt, &session.implt().await) {
+                return Signal::NotMatch;
+            }
+        }
+        Signal::Matched
+    }
+}
+
+/// 匹配指定 type 的事件
+pub fn on_event<S: ToString>(ty: S) -> EventTypeRule {
+    EventTypeRule {
+        ty: Some(ty.to_string()),
+        ..Default::default()
+    }
+}
+
+/// 匹配消息事件
+pub fn on_message() -> EventTypeRule {
+    on_event("message")
+}
+
+/// 匹配指定 detail_type 的通知事件
+pub fn on_notice<S: ToString>(detail_type: S) -> EventTypeRule {
+    on_event("notice").detail_type(detail_type)
+}
+
+/// 匹配请求事件
+pub fn on_request() -> EventTypeRule {
+    on_event("request")
+}
+
+/// 匹配指定平台的事件
+pub fn on_platform<S: ToString>(platform: S) -> EventTypeRule {
+    EventTypeRule::default().platform(platform)
+}
+
+/// 匹配指定实现端的事件
+pub fn on_impl<S: ToString>(implt: S) -> EventTypeRule {
+    EventTypeRule::default().implt(implt)
+}
+
+/// 匹配可以解析为 `E` 的事件，如 `is::<GroupMessageEvent>()`
+///
+/// 先检查 type、detail_type 与 sub_type，均匹配时才复制 event 进行完整解析。
+pub struct IsEvent<E>(PhantomData<fn() -> E>);
+
+/// `header` 的类型字段是否与 `L` 声明的一致，字段缺失等错误留给完整解析
+fn declared<L, X: TryFromEvent<L>>(header: &mut Event) -> bool {
+    !matches!(
+        X::try_from_event_mut(header, ""),
+        Err(WalleError::DeclareNotMatch(..))
+    )
+}
+
+#[async_trait]
+impl<T, D, S, P, I> Rule for IsEvent<BaseEvent<T, D, S, P, I>>
+where
+    T: TryFromEvent<TypeLevel>,
+    D: TryFromEvent<DetailTypeLevel>,
+    S: TryFromEvent<SubTypeLevel>,
+    P: TryFromEvent<PlatformLevel>,
+    I: TryFromEvent<ImplLevel>,
+{
+    async fn rule(&self, session: &Session) -> Signal {
+        let event = &session.event;
+        let mut header = Event {
+            id: String::default(),
+            time: event.time,
+            ty: event.ty.clone(),
+            detail_type: event.detail_type.clone(),
+            sub_type: event.sub_type.clone(),
+            extra: Default::default(),
+        };
+        if !(declared::<_, T>(&mut header)
+            && declared::<_, D>(&mut header)
+            && declared::<_, S>(&mut header))
+        {
+            return Signal::NotMatch;
+        }
+        let implt = session.implt().await;
+        match BaseEvent::<T, D, S, P, I>::parse(session.event.clone(), &implt) {
+            Ok(_) => Signal::Matched,
+            Err(_) => Signal::NotMatch,
+        }
+    }
+}
+
+pub fn is<E: ParseEvent>() -> IsEvent<E> {
+    IsEvent(PhantomData)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is, on_event, on_impl, on_message, on_notice, on_platform, on_request};
+    use crate::matcher::mock::{group_message, session, MockCaller};
+    use crate::{Rule, Session, Signal};
+    use std::sync::Arc;
+    use walle_core::{
+        event::{
+            BaseEvent, Event, Group, GroupMessageEvent, Message, PrivateMessageEvent, SubTypeLevel,
+            TryFromEvent,
+        },
+        prelude::WalleError,
+        WalleResult,
+    };
+
+    struct Join;
+
+    impl TryFromEvent<SubTypeLevel> for Join {
+        fn try_from_event_mut(event: &mut Event, _: &str) -> WalleResult<Self> {
+            if event.sub_type == "join" {
+                Ok(Join)
+            } else {
+                Err(WalleError::DeclareNotMatch("join", event.sub_type.clone()))
+            }
+        }
+    }
+
+    fn of(event: Event) -> Session {
+        session(event, Arc::new(MockCaller::default()), Default::default())
+    }
+
+    #[tokio::test]
+    async fn event_types() {
+        let message = of(group_message("user", "hi"));
+        assert_eq!(on_message().rule(&message).await, Signal::Matched);
+        assert_eq!(on_request().rule(&message).await, Signal::NotMatch);
+        assert_eq!(
+            on_notice("group_member_increase").rule(&message).await,
+            Signal::NotMatch
+        );
+        assert_eq!(
+            on_message().detail_type("group").rule(&message).await,
+            Signal::Matched
+        );
+        assert_eq!(
+            on_message().sub_type("join").rule(&message).await,
+            Signal::NotMatch
+        );
+        assert_eq!(on_platform("qq").rule(&message).await, Signal::Matched);
+        assert_eq!(
+            on_platform("telegram").rule(&message).await,
+            Signal::NotMatch
+        );
+        assert_eq!(on_impl("mock").rule(&message).await, Signal::Matched);
+        assert_eq!(on_impl("other").rule(&message).await, Signal::NotMatch);
+
+        let mut notice = group_message("user", "");
+        notice.ty = "notice".to_owned();
+        notice.detail_type = "group_member_increase".to_owned();
+        notice.sub_type = "join".to_owned();
+        let notice = of(notice);
+        assert_eq!(
+            on_notice("group_member_increase").rule(&notice).await,
+            Signal::Matched
+        );
+        assert_eq!(
+            on_event("notice").sub_type("join").rule(&notice).await,
+            Signal::Matched
+        );
+        let mut request = group_message("user", "");
+        request.ty = "request".to_owned();
+        assert_eq!(on_request().rule(&of(request)).await, Signal::Matched);
+    }
+
+    #[tokio::test]
+    async fn is_event() {
+        let message = of(group_message("user", "hi"));
+        assert_eq!(
+            is::<GroupMessageEvent>().rule(&message).await,
+            Signal::Matched
+        );
+        assert_eq!(
+            is::<PrivateMessageEvent>().rule(&message).await,
+            Signal::NotMatch
+        );
+        assert_eq!(
+            is::<BaseEvent<Message, Group, Join>>().rule(&message).await,
+            Signal::NotMatch
+        );
+        let mut event = group_message("user", "hi");
+        event.sub_type = "join".to_owned();
+        assert_eq!(
+            is::<BaseEvent<Message, Group, Join>>()
+                .rule(&of(event))
+                .await,
+            Signal::Matched
+        );
+        let mut event = group_message("user", "hi");
+        event.extra.remove("group_id");
+        assert_eq!(
+            is::<GroupMessageEvent>().rule(&of(event)).await,
+            Signal::NotMatch
+        );
+    }
+}
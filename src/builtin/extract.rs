@@ -1,4 +1,268 @@
+use walle_core::{
+    prelude::async_trait,
+    segment::{File, Image, MessageExt, MsgSegment, MsgSegmentRef, Segments},
+    util::{Value, ValueMapExt},
+    WalleError, WalleResult,
+};
+
+use crate::{utils::plain_text, ChannelRef, FromSessionPart, PluginSection, Session};
+
+/// 以命令名开头的消息，去除命令后剩余的消息段
+///
+/// 命令名可以是字符串或包含别名的数组，如 `["roulette", "轮盘赌"]`，
+/// 匹配时会先去除 `MatchersConfig::command_start` 中的命令前缀。
+/// 命令名匹配后即认领该事件，之后的 extractor 失败将回复命令用法，见 `Session::claim_command`。
+#[macro_export]
+macro_rules! on_command {
+    ($cid: ident, $span: tt; $($subids: ident => $commands: expr),*) => {
+        pub enum $cid {
+            $($subids($span::walle_core::segment::Segments)),*
+        }
+
+        #[$span::walle_core::prelude::async_trait]
+        impl $span::FromSessionPart for $cid {
+            async fn from_session_part(
+                session: &mut $span::Session,
+            ) -> $span::walle_core::WalleResult<Self> {
+                use $span::walle_core::{segment::MessageMutExt, util::ValueMapExt};
+
+                let mut segs = session
+                    .event
+                    .extra
+                    .try_get_as_mut::<&mut Vec<$span::walle_core::util::Value>>("message")
+                    .map(std::mem::take)?
+                    .into_iter()
+                    .map(|seg| seg.downcast())
+                    .collect::<$span::walle_core::WalleResult<$span::walle_core::segment::Segments>>()?;
+                if let Ok(text) = segs.try_first_text_mut() {
+                    $(let names = $span::builtin::CommandNames::command_names(&$commands);
+                    if let Some(mut rest) = $span::builtin::strip_command_prefix(
+                        &session.config,
+                        &session.event,
+                        text,
+                        &names,
+                    ) {
+                        session.claim_command($span::builtin::command_usage(&names));
+                        rest = rest.trim_start();
+                        if !rest.is_empty() {
+                            *text = rest.to_string();
+                        } else {
+                            segs.remove(0);
+                        }
+                        return Ok(Self::$subids(segs));
+                    })*
+                }
+                let mut names = Vec::new();
+                $(names.extend($span::builtin::CommandNames::command_names(&$commands));)*
+                Err($span::walle_core::WalleError::Other(format!(
+                    "Command not match with {}",
+                    names.join(" or ")
+                )))
+            }
+            fn commands() -> Vec<String> {
+                let mut names = Vec::new();
+                $(names.extend($span::builtin::CommandNames::command_names(&$commands));)*
+                names.into_iter().map(String::from).collect()
+            }
+        }
+    };
+    ($cid: ident, $command: expr) => {
+        $crate::on_command!($cid, $command, walle);
+    };
+    ($cid: ident, $command: expr, $span: tt) => {
+        pub struct $cid($span::walle_core::segment::Segments);
+
+        #[$span::walle_core::prelude::async_trait]
+        impl $span::FromSessionPart for $cid {
+            async fn from_session_part(
+                session: &mut $span::Session,
+            ) -> $span::walle_core::WalleResult<Self> {
+                use $span::walle_core::{segment::MessageMutExt, util::ValueMapExt};
+                let mut segs = session
+                    .event
+                    .extra
+                    .try_get_as_mut::<&mut Vec<$span::walle_core::util::Value>>("message")
+                    .map(std::mem::take)?
+                    .into_iter()
+                    .map(|seg| seg.downcast())
+                    .collect::<$span::walle_core::WalleResult<$span::walle_core::segment::Segments>>()?;
+                if let Ok(text) = segs.try_first_text_mut() {
+                    let names = $span::builtin::CommandNames::command_names(&$command);
+                    if let Some(mut rest) = $span::builtin::strip_command_prefix(
+                        &session.config,
+                        &session.event,
+                        text,
+                        &names,
+                    ) {
+                        session.claim_command($span::builtin::command_usage(&names));
+                        rest = rest.trim_start();
+                        if !rest.is_empty() {
+                            *text = rest.to_string();
+                        } else {
+                            segs.remove(0);
+                        }
+                        return Ok(Self(segs));
+                    }
+                }
+                Err($span::walle_core::WalleError::Other(format!(
+                    "Command not match with {}",
+                    $span::builtin::CommandNames::command_names(&$command).join(" or ")
+                )))
+            }
+            fn commands() -> Vec<String> {
+                $span::builtin::CommandNames::command_names(&$command)
+                    .into_iter()
+                    .map(String::from)
+                    .collect()
+            }
+        }
+    };
+    ($cid: ident, $($subids: ident => $commands: expr),*) => {
+        $crate::on_command!($cid, walle; $($subids => $commands),*);
+    };
+}
+
+// 以下 extractor 均只读取 event，不会修改 session
+
+/// 发送者 user_id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UserId(pub String);
+
+/// 群组 group_id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GroupId(pub String);
+
+/// bot 自身 user_id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SelfId(pub String);
+
+/// 消息 message_id
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MessageId(pub String);
+
+/// 消息的文本替代表示 alt_message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AltMessage(pub String);
+
+/// 所有 text 消息段拼接而成的文本，首尾空白会被去除
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlainText(pub String);
+
+/// 所有 mention 消息段的 user_id
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Mentions(pub Vec<String>);
+
+/// 所有 image 消息段
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Images(pub Vec<Image>);
+
+/// 所有 file 消息段
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Files(pub Vec<File>);
+
+macro_rules! field_extractor {
+    ($ty: ident, $key: expr) => {
+        #[async_trait]
+        impl FromSessionPart for $ty {
+            async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+                session.event.extra.get_downcast($key).map(Self)
+            }
+        }
+    };
+}
+
+field_extractor!(UserId, "user_id");
+field_extractor!(GroupId, "group_id");
+field_extractor!(MessageId, "message_id");
+field_extractor!(AltMessage, "alt_message");
+
+#[async_trait]
+impl FromSessionPart for SelfId {
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        session
+            .event
+            .self_id()
+            .map(Self)
+            .ok_or_else(|| WalleError::MapMissedKey("self".to_owned()))
+    }
+}
+
+#[async_trait]
+impl FromSessionPart for ChannelRef {
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        let extra = &session.event.extra;
+        Ok(Self {
+            guild_id: extra.get_downcast("guild_id")?,
+            channel_id: extra.get_downcast("channel_id")?,
+        })
+    }
+}
+
+#[async_trait]
+impl FromSessionPart for PlainText {
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        plain_text(&session.event)
+            .map(|s| Self(s.trim().to_owned()))
+            .ok_or_else(|| WalleError::MapMissedKey("message".to_owned()))
+    }
+}
+
+#[async_trait]
+impl FromSessionPart for Mentions {
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        let segs = session
+            .event
+            .extra
+            .try_get_as_ref::<&Vec<Value>>("message")?;
+        Ok(Self(
+            segs.iter()
+                .filter_map(|seg| match seg.try_as_ref::<MsgSegmentRef<'_>>() {
+                    Ok(MsgSegmentRef::Mention { user_id, .. }) => Some(user_id.to_owned()),
+                    _ => None,
+                })
+                .collect(),
+        ))
+    }
+}
+
+fn segments(session: &Session) -> WalleResult<Segments> {
+    session
+        .event
+        .extra
+        .try_get_as_ref::<&Vec<Value>>("message")?
+        .iter()
+        .cloned()
+        .map(MsgSegment::try_from)
+        .collect()
+}
+
+#[async_trait]
+impl FromSessionPart for Images {
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        segments(session).map(|segs| Self(segs.extract()))
+    }
+}
+
+#[async_trait]
+impl FromSessionPart for Files {
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        segments(session).map(|segs| Self(segs.extract()))
+    }
+}
+
+/// 当前配置中的插件配置段，配置重新加载后自动更新
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PluginConfig<T>(pub T);
+
+#[async_trait]
+impl<T: PluginSection> FromSessionPart for PluginConfig<T> {
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        session.config.plugin().map(Self)
+    }
+}
+
 #[cfg(test)]
+#[allow(dead_code)]
 mod test {
     pub struct Command(walle_core::segment::Segments);
 
@@ -10,7 +274,7 @@ mod test {
                 .event
                 .extra
                 .try_get_as_mut::<&mut Vec<walle_core::util::Value>>("message")
-                .map(|v| std::mem::take(v))?
+                .map(std::mem::take)?
                 .into_iter()
                 .map(|seg| seg.downcast())
                 .collect::<walle_core::WalleResult<walle_core::segment::Segments>>()?;
@@ -88,7 +352,7 @@ mod test {
                 .event
                 .extra
                 .try_get_as_mut::<&mut Vec<walle_core::util::Value>>("message")
-                .map(|v| std::mem::take(v))?
+                .map(std::mem::take)?
                 .into_iter()
                 .map(|seg| seg.downcast())
                 .collect::<walle_core::WalleResult<walle_core::segment::Segments>>()?;
@@ -106,84 +370,128 @@ mod test {
             )))
         }
     }
-}
 
-#[macro_export]
-macro_rules! on_command {
-    ($cid: ident, $command: expr) => {
-        on_command!($cid, $command, walle);
-    };
-    ($cid: ident, $command: expr, $span: tt) => {
-        pub struct $cid($span::walle_core::segment::Segments);
+    crate::on_command!(Echo, "echo", crate);
+    crate::on_command!(Roulette, ["roulette", "轮盘赌"], crate);
+    crate::on_command!(Sub, crate; Add => "add", Remove => ["remove", "rm"]);
 
-        #[$span::walle_core::prelude::async_trait]
-        impl $span::FromSessionPart for $cid {
-            async fn from_session_part(
-                session: &mut $span::Session,
-            ) -> $span::walle_core::WalleResult<Self> {
-                use $span::walle_core::{segment::MessageMutExt, util::ValueMapExt};
-                let mut segs = session
-                    .event
-                    .extra
-                    .try_get_as_mut::<&mut Vec<$span::walle_core::util::Value>>("message")
-                    .map(|v| std::mem::take(v))?
-                    .into_iter()
-                    .map(|seg| seg.downcast())
-                    .collect::<$span::walle_core::WalleResult<$span::walle_core::segment::Segments>>()?;
-                if let Ok(text) = segs.try_first_text_mut() {
-                    if let Some(mut rest) = text.strip_prefix($command) {
-                        rest = rest.trim_start();
-                        if !rest.is_empty() {
-                            *text = rest.to_string();
-                        } else {
-                            segs.remove(0);
-                        }
-                        return Ok(Self(segs));
-                    }
-                }
-                Err($span::walle_core::WalleError::Other(format!(
-                    "Command not match with {}",
-                    $command
-                )))
-            }
-        }
-    };
-    ($cid: ident, $($subids: ident => $commands: expr),*) => {
-        pub enum $cid {
-            $($subids(walle::walle_core::segment::Segments)),*
-        }
+    fn typed_extractors() -> impl crate::MatcherHandler {
+        use super::{GroupId, Mentions, PlainText, UserId};
+        crate::matcher(
+            |_: UserId,
+             _: Option<GroupId>,
+             _: Result<crate::ChannelRef, walle_core::WalleError>,
+             _: Mentions,
+             _: PlainText| async {},
+        )
+    }
 
-        #[walle::walle_core::prelude::async_trait]
-        impl walle::FromSessionPart for $cid {
-            async fn from_session_part(
-                session: &mut walle::Session,
-            ) -> walle::walle_core::WalleResult<Self> {
-                use walle::walle_core::{segment::MessageMutExt, util::ValueMapExt};
+    fn of(event: walle_core::event::Event) -> crate::Session {
+        use crate::matcher::mock::{session, MockCaller};
+        session(
+            event,
+            std::sync::Arc::new(MockCaller::default()),
+            Default::default(),
+        )
+    }
 
-                let mut segs = session
-                    .event
-                    .extra
-                    .try_get_as_mut::<&mut Vec<walle::walle_core::util::Value>>("message")
-                    .map(std::mem::take)?
-                    .into_iter()
-                    .map(|seg| seg.downcast())
-                    .collect::<walle::walle_core::WalleResult<walle::walle_core::segment::Segments>>()?;
-                if let Ok(text) = segs.try_first_text_mut() {
-                    $(if let Some(mut rest) = text.strip_prefix($commands) {
-                        rest = rest.trim_start();
-                        if !rest.is_empty() {
-                            *text = rest.to_string();
-                        } else {
-                            segs.remove(0);
-                        }
-                        return Ok(Self::$subids(segs));
-                    })*
-                }
-                Err(walle::walle_core::WalleError::Other(format!(
-                    "Command not match with {}",
-                    [$($commands,)*].join(" or ")
-                )))
+    async fn extract<T: crate::FromSessionPart>(
+        event: walle_core::event::Event,
+    ) -> walle_core::WalleResult<T> {
+        T::from_session_part(&mut of(event)).await
+    }
+
+    #[tokio::test]
+    async fn group_message_parts() {
+        use super::{AltMessage, GroupId, Images, Mentions, MessageId, PlainText, UserId};
+        use crate::matcher::mock::{group_message, mention_seg, text_seg, with_message};
+        use walle_core::{segment::Image, util::Value, value_map};
+
+        let event = || group_message("user", "  hi  ");
+        assert_eq!(extract::<UserId>(event()).await.unwrap().0, "user");
+        assert_eq!(extract::<GroupId>(event()).await.unwrap().0, "group");
+        assert_eq!(extract::<MessageId>(event()).await.unwrap().0, "1");
+        assert_eq!(extract::<AltMessage>(event()).await.unwrap().0, "  hi  ");
+        assert_eq!(extract::<PlainText>(event()).await.unwrap().0, "hi");
+        assert_eq!(
+            extract::<Mentions>(event()).await.unwrap().0,
+            Vec::<String>::new()
+        );
+        assert_eq!(extract::<Images>(event()).await.unwrap().0, vec![]);
+
+        let image =
+            Value::Map(value_map! { "type": "image", "data": value_map! { "file_id": "f" } });
+        let event = with_message(
+            event(),
+            vec![
+                mention_seg("a"),
+                text_seg(" x "),
+                image,
+                mention_seg("b"),
+                text_seg("y"),
+            ],
+        );
+        assert_eq!(
+            extract::<Mentions>(event.clone()).await.unwrap().0,
+            ["a", "b"]
+        );
+        assert_eq!(
+            extract::<Images>(event.clone()).await.unwrap().0,
+            [Image {
+                file_id: "f".to_owned()
+            }]
+        );
+        assert_eq!(extract::<PlainText>(event).await.unwrap().0, "x y");
+    }
+
+    #[tokio::test]
+    async fn channel_ref() {
+        use crate::{matcher::mock::group_message, ChannelRef};
+        use walle_core::util::Value;
+
+        assert!(extract::<ChannelRef>(group_message("user", "hi"))
+            .await
+            .is_err());
+        let mut event = group_message("user", "hi");
+        event
+            .extra
+            .insert("guild_id".to_owned(), Value::Str("g".to_owned()));
+        event
+            .extra
+            .insert("channel_id".to_owned(), Value::Str("c".to_owned()));
+        assert_eq!(
+            extract::<ChannelRef>(event).await.unwrap(),
+            ChannelRef {
+                guild_id: "g".to_owned(),
+                channel_id: "c".to_owned()
             }
-        }
+        );
+    }
+
+    #[tokio::test]
+    async fn optional_parts() {
+        use super::{GroupId, UserId};
+        use crate::{matcher::mock::group_message, ChannelRef};
+        use walle_core::WalleError;
+
+        let mut private = group_message("user", "hi");
+        private.detail_type = "private".to_owned();
+        private.extra.remove("group_id");
+        assert!(extract::<GroupId>(private.clone()).await.is_err());
+        assert_eq!(
+            extract::<Option<GroupId>>(private.clone()).await.unwrap(),
+            None
+        );
+        assert_eq!(
+            extract::<Option<GroupId>>(group_message("user", "hi"))
+                .await
+                .unwrap(),
+            Some(GroupId("group".to_owned()))
+        );
+        assert!(extract::<Result<ChannelRef, WalleError>>(private.clone())
+            .await
+            .unwrap()
+            .is_err());
+        assert_eq!(extract::<UserId>(private).await.unwrap().0, "user");
     }
 }
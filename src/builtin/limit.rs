@@ -0,0 +1,468 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use dashmap::{mapref::entry::Entry, DashMap};
+use walle_core::{
+    prelude::{async_trait, WalleError},
+    util::ValueMapExt,
+    WalleResult,
+};
+
+use crate::{ActionCaller, Charge, MatchersHook, Rule, Session, Signal};
+
+/// 频率限制的统计范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scope {
+    /// 每个用户独立计数
+    User,
+    /// 每个群组或频道独立计数，私聊时按用户计数
+    Group,
+    /// 全局共享计数
+    Global,
+}
+
+impl Scope {
+    fn key(&self, session: &Session) -> Option<String> {
+        let extra = &session.event.extra;
+        let user_id = || extra.get_downcast::<String>("user_id").ok();
+        match self {
+            Scope::User => user_id(),
+            Scope::Group => extra
+                .get_downcast::<String>("group_id")
+                .ok()
+                .or_else(|| {
+                    let guild_id = extra.get_downcast::<String>("guild_id").ok()?;
+                    let channel_id = extra.get_downcast::<String>("channel_id").ok()?;
+                    Some(format!("{}:{}", guild_id, channel_id))
+                })
+                .or_else(user_id),
+            Scope::Global => Some(String::default()),
+        }
+    }
+}
+
+type CooldownReply = Arc<dyn Fn(Duration) -> String + Send + Sync + 'static>;
+
+/// 冷却时间 Rule
+///
+/// rule 阶段只登记，handler 实际执行时才检查并开始计算冷却，冷却中时不执行 handler，如
+/// `start_with("draw").with(cooldown(Scope::User, Duration::from_secs(30)))`。
+/// 作为 `Matchers::with_global_rule` 时在全局阶段对每个 event 扣除一次。
+#[derive(Clone)]
+pub struct Cooldown {
+    pub scope: Scope,
+    pub duration: Duration,
+    last: Arc<DashMap<String, Instant>>,
+    pruned: Arc<Mutex<Instant>>,
+    reply: Option<CooldownReply>,
+}
+
+impl Cooldown {
+    /// 冷却中时回复消息，参数为剩余冷却时间
+    pub fn reply<F>(self, reply: F) -> Self
+    where
+        F: Fn(Duration) -> String + Send + Sync + 'static,
+    {
+        Self {
+            reply: Some(Arc::new(reply)),
+            ..self
+        }
+    }
+
+    /// 每隔 `duration` 清理一次已结束冷却的记录
+    fn prune(&self, now: Instant) {
+        let mut pruned = self.pruned.lock().unwrap_or_else(|e| e.into_inner());
+        if now.duration_since(*pruned) >= self.duration {
+            *pruned = now;
+            self.last
+                .retain(|_, last| now.duration_since(*last) < self.duration);
+        }
+    }
+}
+
+#[async_trait]
+impl Rule for Cooldown {
+    async fn rule(&self, session: &Session) -> Signal {
+        let Some(key) = self.scope.key(session) else {
+            return Signal::NotMatch;
+        };
+        session.defer_charge(Arc::new(self.clone()), key);
+        Signal::Matched
+    }
+}
+
+impl Charge for Cooldown {
+    fn try_consume(&self, key: &str, _: &Session) -> Result<(), Option<String>> {
+        let now = Instant::now();
+        match self.last.entry(key.to_owned()) {
+            Entry::Occupied(last) if now.duration_since(*last.get()) < self.duration => {
+                let remaining = self.duration - now.duration_since(*last.get());
+                return Err(self.reply.as_ref().map(|reply| reply(remaining)));
+            }
+            Entry::Occupied(mut last) => {
+                last.insert(now);
+            }
+            Entry::Vacant(last) => {
+                last.insert(now);
+            }
+        }
+        self.prune(now);
+        Ok(())
+    }
+
+    fn refund(&self, key: &str, _: &Session) {
+        // 扣除成功时该 key 不在冷却中，移除即恢复原状
+        self.last.remove(key);
+    }
+}
+
+/// 每个 `scope` 在 `duration` 内最多执行一次
+pub fn cooldown(scope: Scope, duration: Duration) -> Cooldown {
+    Cooldown {
+        scope,
+        duration,
+        last: Arc::default(),
+        pruned: Arc::new(Mutex::new(Instant::now())),
+        reply: None,
+    }
+}
+
+type QuotaReply = Arc<dyn Fn(u32, Duration) -> String + Send + Sync + 'static>;
+
+/// 持久化计数前等待的时间，期间的多次计数合并写入
+const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// 使用次数限制 Rule
+///
+/// 以 `period` 为周期重置计数，周期按 `MatchersConfig::utc_offset` 的本地时间对齐，
+/// 如一天的周期在本地零点重置。与 `Cooldown` 相同，handler 实际执行时才计数，检查与计数为同一原子操作。
+#[derive(Clone)]
+pub struct Quota {
+    pub scope: Scope,
+    pub count: u32,
+    pub period: Duration,
+    /// key -> (周期序号, 已使用次数)
+    used: Arc<DashMap<String, (u64, u32)>>,
+    /// 最近一次清理过期计数时的周期序号
+    pruned: Arc<AtomicU64>,
+    reply: Option<QuotaReply>,
+    persist: Option<PathBuf>,
+    saving: Arc<AtomicBool>,
+}
+
+impl Quota {
+    /// 次数用尽时回复消息，参数为次数上限与距离重置的时间
+    pub fn reply<F>(self, reply: F) -> Self
+    where
+        F: Fn(u32, Duration) -> String + Send + Sync + 'static,
+    {
+        Self {
+            reply: Some(Arc::new(reply)),
+            ..self
+        }
+    }
+
+    /// 将计数保存至 `path`
+    ///
+    /// 需通过 `Matchers::add_hook` 注册该 Quota，启动时从 `path` 恢复计数，关闭时写入未保存的计数。
+    pub fn persist<P: Into<PathBuf>>(self, path: P) -> Self {
+        Self {
+            persist: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// 从 `persist` 的文件恢复计数，已有的计数不会被覆盖
+    pub async fn load(&self) -> WalleResult<()> {
+        let Some(path) = &self.persist else {
+            return Ok(());
+        };
+        let data = match tokio::fs::read(path).await {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => {
+                return Err(WalleError::Other(format!(
+                    "load quota from {:?} failed: {}",
+                    path, e
+                )))
+            }
+        };
+        let used: HashMap<String, (u64, u32)> = serde_json::from_slice(&data)
+            .map_err(|e| WalleError::Other(format!("load quota from {:?} failed: {}", path, e)))?;
+        for (key, used) in used {
+            self.used.entry(key).or_insert(used);
+        }
+        Ok(())
+    }
+
+    /// 立即将计数写入 `persist` 的文件
+    pub async fn flush(&self) -> WalleResult<()> {
+        let Some(path) = &self.persist else {
+            return Ok(());
+        };
+        let used: HashMap<String, (u64, u32)> = self
+            .used
+            .iter()
+            .map(|r| (r.key().clone(), *r.value()))
+            .collect();
+        let data = serde_json::to_vec(&used).map_err(|e| WalleError::Other(e.to_string()))?;
+        tokio::fs::write(path, data)
+            .await
+            .map_err(|e| WalleError::Other(format!("save quota to {:?} failed: {}", path, e)))
+    }
+
+    /// 在后台合并写入计数
+    fn save(&self) {
+        if self.persist.is_none() || self.saving.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let quota = self.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(SAVE_DELAY).await;
+            quota.saving.store(false, Ordering::Release);
+            if let Err(e) = quota.flush().await {
+                tracing::warn!(target: "Walle", "{}", e);
+            }
+        });
+    }
+
+    /// 返回 (当前周期序号, 距离下一周期的时间)
+    fn window(&self, session: &Session) -> (u64, Duration) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let offset = session.config.utc_offset.whole_seconds() as i64;
+        let local = Duration::from_secs((now.as_secs() as i64 + offset).max(0) as u64)
+            + Duration::from_nanos(now.subsec_nanos() as u64);
+        let period = self.period.as_secs().max(1);
+        let window = local.as_secs() / period;
+        let reset_in = Duration::from_secs((window + 1) * period).saturating_sub(local);
+        (window, reset_in)
+    }
+}
+
+#[async_trait]
+impl Rule for Quota {
+    async fn rule(&self, session: &Session) -> Signal {
+        let Some(key) = self.scope.key(session) else {
+            return Signal::NotMatch;
+        };
+        session.defer_charge(Arc::new(self.clone()), key);
+        Signal::Matched
+    }
+}
+
+impl Charge for Quota {
+    fn try_consume(&self, key: &str, session: &Session) -> Result<(), Option<String>> {
+        let (window, reset_in) = self.window(session);
+        if self.pruned.swap(window, Ordering::AcqRel) != window {
+            self.used.retain(|_, used| used.0 == window);
+        }
+        {
+            let mut used = self.used.entry(key.to_owned()).or_insert((window, 0));
+            if used.0 != window {
+                *used = (window, 0);
+            }
+            if used.1 >= self.count {
+                return Err(self.reply.as_ref().map(|reply| reply(self.count, reset_in)));
+            }
+            used.1 += 1;
+        }
+        self.save();
+        Ok(())
+    }
+
+    fn refund(&self, key: &str, session: &Session) {
+        let (window, _) = self.window(session);
+        if let Some(mut used) = self.used.get_mut(key) {
+            if used.0 == window {
+                used.1 = used.1.saturating_sub(1);
+            }
+        }
+        self.save();
+    }
+}
+
+#[async_trait]
+impl MatchersHook for Quota {
+    async fn on_start(&self, _: &Arc<dyn ActionCaller + Send + 'static>) {
+        if let Err(e) = self.load().await {
+            tracing::warn!(target: "Walle", "{}", e);
+        }
+    }
+    async fn on_shutdown(&self, _: &Arc<dyn ActionCaller + Send + 'static>) {
+        if let Err(e) = self.flush().await {
+            tracing::warn!(target: "Walle", "{}", e);
+        }
+    }
+}
+
+/// 每个 `scope` 在每个 `period` 内最多执行 `count` 次
+pub fn quota(scope: Scope, count: u32, period: Duration) -> Quota {
+    Quota {
+        scope,
+        count,
+        period,
+        used: Arc::default(),
+        pruned: Arc::default(),
+        reply: None,
+        persist: None,
+        saving: Arc::default(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{cooldown, quota, Scope};
+    use crate::matcher::mock::{group_message, session, MockCaller};
+    use crate::{MatchersConfig, Rule, Signal};
+    use std::{sync::Arc, time::Duration};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_charge() {
+        let caller = Arc::new(MockCaller::default());
+        let limit = quota(Scope::User, 5, Duration::from_secs(86400));
+        let tasks: Vec<_> = (0..32)
+            .map(|_| {
+                let (limit, caller) = (limit.clone(), caller.clone());
+                tokio::spawn(async move {
+                    let s = session(group_message("user", "draw"), caller, Default::default());
+                    limit.rule(&s).await;
+                    s.charge().is_ok()
+                })
+            })
+            .collect();
+        let mut charged = 0;
+        for task in tasks {
+            charged += usize::from(task.await.unwrap());
+        }
+        assert_eq!(charged, 5);
+    }
+
+    #[tokio::test]
+    async fn refund_on_rejection() {
+        let caller = Arc::new(MockCaller::default());
+        let cool = cooldown(Scope::User, Duration::from_secs(60));
+        let limit = quota(Scope::User, 1, Duration::from_secs(86400));
+        let s = session(
+            group_message("user", "draw"),
+            caller.clone(),
+            Default::default(),
+        );
+        limit.rule(&s).await;
+        assert_eq!(s.charge(), Ok(()));
+        let s = session(
+            group_message("user", "draw"),
+            caller.clone(),
+            Default::default(),
+        );
+        cool.rule(&s).await;
+        limit.rule(&s).await;
+        assert_eq!(s.charge(), Err(None));
+        // quota 不足时退还已扣除的冷却
+        let s = session(group_message("user", "draw"), caller, Default::default());
+        cool.rule(&s).await;
+        assert_eq!(s.charge(), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn global_rule() {
+        use crate::builtin::start_with;
+        use crate::matcher::mock::dispatch;
+        use crate::{matcher, MatcherHandler, Matchers, Session};
+
+        let draw = || {
+            start_with("draw")
+                .layer(matcher(|_: Session| async { "drawn" }).awaited())
+                .boxed()
+        };
+        let matchers = Matchers::default()
+            .with_global_rule(
+                cooldown(Scope::User, Duration::from_secs(60)).reply(|_| "cooling".to_owned()),
+            )
+            .add_matcher(draw())
+            .add_matcher(draw());
+        let caller = Arc::new(MockCaller::default());
+        let events = vec![group_message("user", "draw"), group_message("user", "draw")];
+        dispatch(&matchers, caller.clone(), Default::default(), events).await;
+        assert_eq!(caller.sent(), vec!["drawn", "drawn", "cooling"]);
+    }
+
+    #[tokio::test]
+    async fn persist_round_trip() {
+        let path = crate::matcher::mock::temp_path("quota.json");
+        let caller = Arc::new(MockCaller::default());
+        let limit = quota(Scope::User, 2, Duration::from_secs(86400)).persist(&path);
+        let s = session(
+            group_message("user", "draw"),
+            caller.clone(),
+            Default::default(),
+        );
+        limit.rule(&s).await;
+        assert_eq!(s.charge(), Ok(()));
+        limit.flush().await.unwrap();
+
+        let restored = quota(Scope::User, 2, Duration::from_secs(86400)).persist(&path);
+        restored.load().await.unwrap();
+        let s = session(
+            group_message("user", "draw"),
+            caller.clone(),
+            Default::default(),
+        );
+        restored.rule(&s).await;
+        assert_eq!(s.charge(), Ok(()));
+        let s = session(group_message("user", "draw"), caller, Default::default());
+        restored.rule(&s).await;
+        assert_eq!(s.charge(), Err(None));
+        std::fs::remove_file(path).ok();
+    }
+
+    #[tokio::test]
+    async fn charge_on_dispatch() {
+        let caller = Arc::new(MockCaller::default());
+        let config = MatchersConfig::default();
+        let limit = cooldown(Scope::User, Duration::from_secs(60)).reply(|_| "cooling".to_owned());
+
+        let s = session(
+            group_message("user", "draw"),
+            caller.clone(),
+            config.clone(),
+        );
+        assert_eq!(limit.rule(&s).await, Signal::Matched);
+        let s = session(
+            group_message("user", "draw"),
+            caller.clone(),
+            config.clone(),
+        );
+        assert_eq!(limit.rule(&s).await, Signal::Matched);
+        assert_eq!(s.charge(), Ok(()));
+        let s = session(
+            group_message("user", "draw"),
+            caller.clone(),
+            config.clone(),
+        );
+        assert_eq!(limit.rule(&s).await, Signal::Matched);
+        assert_eq!(s.charge(), Err(Some("cooling".to_owned())));
+
+        let limit = quota(Scope::Group, 1, Duration::from_secs(86400)).reply(|count, reset_in| {
+            format!("{} {}", count, reset_in <= Duration::from_secs(86400))
+        });
+        let s = session(
+            group_message("user", "draw"),
+            caller.clone(),
+            config.clone(),
+        );
+        limit.rule(&s).await;
+        assert_eq!(s.charge(), Ok(()));
+        let s = session(group_message("other", "draw"), caller, config);
+        limit.rule(&s).await;
+        assert_eq!(s.charge(), Err(Some("1 true".to_owned())));
+    }
+}
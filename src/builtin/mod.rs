@@ -1,9 +1,25 @@
+mod calendar;
+mod command;
 mod echo;
+mod event;
 mod extract;
+mod limit;
+mod permission;
 mod pre_handle;
+mod regex;
+mod reply;
 mod rule;
+mod text;
 
+pub use self::regex::*;
+pub use calendar::*;
+pub use command::*;
 pub use echo::*;
+pub use event::*;
 pub use extract::*;
+pub use limit::*;
+pub use permission::*;
 pub use pre_handle::*;
+pub use reply::*;
 pub use rule::*;
+pub use text::*;
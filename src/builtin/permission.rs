@@ -0,0 +1,236 @@
+use serde::{Deserialize, Serialize};
+use walle_core::{
+    action::Action,
+    prelude::async_trait,
+    util::{Value, ValueMap, ValueMapExt},
+    value_map, WalleError, WalleResult,
+};
+
+use crate::{ActionCaller, FromSessionPart, Rule, Session, Signal};
+
+/// 群组或频道中的成员身份
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Member,
+    Admin,
+    Owner,
+}
+
+impl Role {
+    fn parse(s: &str) -> Self {
+        match s {
+            "owner" => Self::Owner,
+            "admin" | "administrator" => Self::Admin,
+            _ => Self::Member,
+        }
+    }
+}
+
+/// 权限要求
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// `MatchersConfig::superusers` 中的用户
+    SuperUser,
+    /// 群主（频道主）
+    Owner,
+    /// 群管理员及以上
+    Admin,
+    /// 任意群组或频道成员
+    Member,
+    /// 私聊
+    Private,
+}
+
+/// 发送者身份
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sender {
+    pub superuser: bool,
+    /// 私聊时为 None
+    pub role: Option<Role>,
+}
+
+impl Sender {
+    pub fn satisfy(&self, permission: Permission) -> bool {
+        match permission {
+            Permission::SuperUser => self.superuser,
+            Permission::Private => self.role.is_none(),
+            Permission::Owner => self.superuser || self.role >= Some(Role::Owner),
+            Permission::Admin => self.superuser || self.role >= Some(Role::Admin),
+            Permission::Member => self.superuser || self.role.is_some(),
+        }
+    }
+
+    /// 解析 session 发送者身份，结果缓存于 session extensions
+    ///
+    /// 优先使用 event 中的 `sender.role` 或 `role` 字段，
+    /// 缺失时调用 `get_group_member_info` 或 `get_guild_member_info` 获取。
+    pub async fn resolve(session: &Session) -> WalleResult<Self> {
+        if let Some(sender) = session.extensions.get::<Self>() {
+            return Ok(sender);
+        }
+        let sender = Self::_resolve(session).await?;
+        session.extensions.insert(sender);
+        Ok(sender)
+    }
+
+    async fn _resolve(session: &Session) -> WalleResult<Self> {
+        let extra = &session.event.extra;
+        let user_id: String = extra.get_downcast("user_id")?;
+        let superuser = session.config.superusers.contains(&user_id);
+        if session.event.detail_type == "private" {
+            return Ok(Self {
+                superuser,
+                role: None,
+            });
+        }
+        let role = match event_role(extra) {
+            Some(role) => role,
+            None => member_role(session, user_id).await?,
+        };
+        Ok(Self {
+            superuser,
+            role: Some(role),
+        })
+    }
+}
+
+/// 通过 `PermissionRule` 时直接使用其解析结果
+#[async_trait]
+impl FromSessionPart for Sender {
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        Self::resolve(session).await
+    }
+}
+
+fn event_role(extra: &ValueMap) -> Option<Role> {
+    extra
+        .try_get_as_ref::<&ValueMap>("sender")
+        .ok()
+        .and_then(|sender| sender.try_get_as_ref::<&str>("role").ok())
+        .or_else(|| extra.try_get_as_ref::<&str>("role").ok())
+        .map(Role::parse)
+}
+
+async fn member_role(session: &Session, user_id: String) -> WalleResult<Role> {
+    let extra = &session.event.extra;
+    let action = if let Ok(group_id) = extra.get_downcast::<String>("group_id") {
+        Action {
+            action: "get_group_member_info".to_owned(),
+            params: value_map! { "group_id": group_id, "user_id": user_id },
+            selft: None,
+        }
+    } else if let Ok(guild_id) = extra.get_downcast::<String>("guild_id") {
+        Action {
+            action: "get_guild_member_info".to_owned(),
+            params: value_map! { "guild_id": guild_id, "user_id": user_id },
+            selft: None,
+        }
+    } else {
+        return Err(WalleError::Other(
+            "event is neither group nor guild".to_owned(),
+        ));
+    };
+    let info = session
+        .call_action(action)
+        .await?
+        .as_result()
+        .map_err(WalleError::RespError)?;
+    Ok(match info {
+        Value::Map(info) => event_role(&info).unwrap_or(Role::Member),
+        _ => Role::Member,
+    })
+}
+
+/// 权限检查 Rule，满足任一权限即匹配
+pub struct PermissionRule {
+    pub permissions: Vec<Permission>,
+}
+
+impl PermissionRule {
+    /// 追加一个可选的权限
+    pub fn or(mut self, permission: Permission) -> Self {
+        self.permissions.push(permission);
+        self
+    }
+}
+
+#[async_trait]
+impl Rule for PermissionRule {
+    async fn rule(&self, session: &Session) -> Signal {
+        match Sender::resolve(session).await {
+            Ok(sender) if self.permissions.iter().any(|p| sender.satisfy(*p)) => Signal::Matched,
+            Ok(_) => Signal::NotMatch,
+            Err(e) => {
+                tracing::debug!(target: "Walle", "resolve sender permission failed: {}", e);
+                Signal::NotMatch
+            }
+        }
+    }
+}
+
+/// 要求发送者具有 `permission` 权限
+pub fn require(permission: Permission) -> PermissionRule {
+    PermissionRule {
+        permissions: vec![permission],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Permission, Role, Sender};
+    use crate::matcher::mock::{group_message, session, MockCaller};
+    use crate::MatchersConfig;
+    use std::sync::Arc;
+    use walle_core::{resp::Resp, util::Value, value_map};
+
+    #[test]
+    fn satisfy() {
+        let admin = Sender {
+            superuser: false,
+            role: Some(Role::Admin),
+        };
+        assert!(admin.satisfy(Permission::Admin));
+        assert!(admin.satisfy(Permission::Member));
+        assert!(!admin.satisfy(Permission::Owner));
+        assert!(!admin.satisfy(Permission::Private));
+        let superuser = Sender {
+            superuser: true,
+            role: None,
+        };
+        assert!(superuser.satisfy(Permission::Owner));
+        assert!(superuser.satisfy(Permission::Private));
+    }
+
+    #[tokio::test]
+    async fn resolve() {
+        let caller = Arc::new(MockCaller::respond(|action| {
+            (action.action == "get_group_member_info")
+                .then(|| Resp::ok(value_map! { "role": "owner" }, ""))
+        }));
+        let config = MatchersConfig {
+            superusers: vec!["root".to_owned()],
+            ..Default::default()
+        };
+
+        let mut event = group_message("user", "hi");
+        event.extra.insert(
+            "sender".to_owned(),
+            Value::Map(value_map! { "role": "administrator" }),
+        );
+        let s = session(event, caller.clone(), config.clone());
+        let sender = Sender::resolve(&s).await.unwrap();
+        assert_eq!(sender.role, Some(Role::Admin));
+        assert!(!sender.superuser);
+        assert!(caller.actions().is_empty());
+
+        let s = session(group_message("root", "hi"), caller.clone(), config);
+        let sender = Sender::resolve(&s).await.unwrap();
+        assert_eq!(sender.role, Some(Role::Owner));
+        assert!(sender.superuser);
+        assert_eq!(caller.actions(), vec!["get_group_member_info"]);
+        Sender::resolve(&s).await.unwrap();
+        assert_eq!(caller.actions().len(), 1);
+    }
+}
@@ -1,5 +1,10 @@
-use crate::{pre_handle_fn, MatchersConfig, PreHandler, Session, Signal};
+use crate::utils::{fold_width, simplify};
+use crate::{pre_handle_fn, PreHandler, Session, Signal};
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
 use walle_core::{
+    event::Event,
+    prelude::async_trait,
     segment::{MessageMutExt, MsgSegmentMut},
     util::{Value, ValueMapExt},
 };
@@ -8,8 +13,9 @@ pub struct StripPrefix {
     pub prefix: String,
 }
 
+#[async_trait]
 impl PreHandler for StripPrefix {
-    fn pre_handle(&self, session: &mut Session) -> Signal {
+    async fn pre_handle(&self, session: &mut Session) -> Signal {
         if let Some(text) = session
             .event
             .extra
@@ -26,6 +32,7 @@ impl PreHandler for StripPrefix {
     }
 }
 
+/// 去除第一个 text 消息段的前缀 `prefix`，与 `start_with` 使用相同的文本
 pub fn strip_prefix<S>(prefix: S) -> StripPrefix
 where
     S: ToString,
@@ -66,10 +73,11 @@ pub fn trim(always_match: bool) -> impl PreHandler {
 
 fn _mention_me(session: &mut Session) -> Signal {
     let self_id = session.event.selft().unwrap_or_default().user_id;
+    let nicknames = session.nicknames();
     let Ok(segs) = session.event.extra.try_get_as_mut::<&mut Vec<Value>>("message") else {
         return Signal::NotMatch
     };
-    _mention_user(segs, self_id) | _nickname(&session.config, segs)
+    _mention_user(segs, self_id) | _nickname(&nicknames, segs)
 }
 
 fn _mention_user(segs: &mut Vec<Value>, user_id: String) -> Signal {
@@ -79,7 +87,7 @@ fn _mention_user(segs: &mut Vec<Value>, user_id: String) -> Signal {
         match seg {
             MsgSegmentMut::Mention {
                 user_id: mention_id,
-            } if mention_id.as_str() == &user_id => {
+            } if mention_id.as_str() == user_id => {
                 mentioned_index = Some(index);
                 break;
             }
@@ -94,9 +102,9 @@ fn _mention_user(segs: &mut Vec<Value>, user_id: String) -> Signal {
     }
 }
 
-fn _nickname(config: &MatchersConfig, segs: &mut Vec<Value>) -> Signal {
+fn _nickname(nicknames: &[String], segs: &mut Vec<Value>) -> Signal {
     if let Ok(text) = segs.try_first_text_mut() {
-        for nickname in &config.nicknames {
+        for nickname in nicknames {
             if let Some(s) = text.strip_prefix(nickname) {
                 if !s.is_empty() {
                     *text = s.to_owned();
@@ -130,6 +138,150 @@ pub fn to_me() -> impl PreHandler {
         } else {
             Signal::NotMatch
         };
-        sig | _mention_me(session)
+        sig | _mention_me(session) | super::reply::_reply_to_me(session)
     })
 }
+
+/// 文本规范化，作用于 message 中所有 text 消息段
+///
+/// 可作为 pre-handler 使用，或配置于 `MatchersConfig::normalize` 对所有事件生效。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Normalize {
+    /// Unicode NFKC 规范化，同时会将全角字母数字转换为半角
+    pub nfkc: bool,
+    /// 全角 ASCII 字符与全角空格转换为半角
+    pub half_width: bool,
+    /// 移除零宽字符，保留组成 emoji 序列的零宽连接符（U+200D）
+    pub strip_zero_width: bool,
+    /// 将连续空白合并为一个空格，保留换行，会破坏代码块等依赖缩进的文本
+    pub collapse_whitespace: bool,
+    /// 将常用繁体字转换为简体字
+    pub simplified: bool,
+}
+
+impl Default for Normalize {
+    fn default() -> Self {
+        Self {
+            nfkc: true,
+            half_width: true,
+            strip_zero_width: true,
+            collapse_whitespace: false,
+            simplified: false,
+        }
+    }
+}
+
+impl Normalize {
+    pub fn nfkc(self, nfkc: bool) -> Self {
+        Self { nfkc, ..self }
+    }
+    pub fn half_width(self, half_width: bool) -> Self {
+        Self { half_width, ..self }
+    }
+    pub fn strip_zero_width(self, strip_zero_width: bool) -> Self {
+        Self {
+            strip_zero_width,
+            ..self
+        }
+    }
+    pub fn collapse_whitespace(self, collapse_whitespace: bool) -> Self {
+        Self {
+            collapse_whitespace,
+            ..self
+        }
+    }
+    pub fn simplified(self, simplified: bool) -> Self {
+        Self { simplified, ..self }
+    }
+
+    /// 规范化单段文本
+    pub fn text(&self, text: &str) -> String {
+        let mut s: String = if self.nfkc {
+            text.nfkc().collect()
+        } else {
+            text.to_owned()
+        };
+        if self.half_width {
+            s = fold_width(&s);
+        }
+        if self.strip_zero_width {
+            s.retain(|c| !matches!(c, '\u{200B}' | '\u{200C}' | '\u{2060}' | '\u{FEFF}'));
+        }
+        if self.simplified {
+            s = s.chars().map(simplify).collect();
+        }
+        if self.collapse_whitespace {
+            let mut collapsed = String::with_capacity(s.len());
+            for c in s.chars() {
+                if c == '\n' {
+                    collapsed.truncate(collapsed.trim_end_matches(' ').len());
+                    collapsed.push(c);
+                } else if !c.is_whitespace() {
+                    collapsed.push(c);
+                } else if !collapsed.ends_with([' ', '\n']) {
+                    collapsed.push(' ');
+                }
+            }
+            s = collapsed;
+        }
+        s
+    }
+
+    /// 规范化 event 中的所有 text 消息段，有修改时返回 true
+    pub fn apply(&self, event: &mut Event) -> bool {
+        let Ok(segs) = event.extra.try_get_as_mut::<&mut Vec<Value>>("message") else {
+            return false;
+        };
+        let Ok(segs) = segs.try_as_mut() else {
+            return false;
+        };
+        let mut changed = false;
+        for seg in segs {
+            if let MsgSegmentMut::Text { text } = seg {
+                let normalized = self.text(text);
+                if normalized != *text {
+                    *text = normalized;
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+}
+
+#[async_trait]
+impl PreHandler for Normalize {
+    async fn pre_handle(&self, session: &mut Session) -> Signal {
+        self.apply(&mut session.event);
+        Signal::Matched
+    }
+}
+
+/// 使用默认配置的文本规范化 pre-handler，总是匹配
+pub fn normalize() -> Normalize {
+    Normalize::default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::normalize;
+
+    #[test]
+    fn normalize_text() {
+        let n = normalize();
+        assert_eq!(n.text("／ｒｏｌｌ\u{200B}　１２！"), "/roll 12!");
+        assert_eq!(n.text("a  b\n    c"), "a  b\n    c");
+        let collapse = n.clone().collapse_whitespace(true);
+        assert_eq!(collapse.text("／ｒｏｌｌ　　１２！"), "/roll 12!");
+        assert_eq!(collapse.text("echo  a \n\n  b"), "echo a\n\nb");
+        // 零宽连接符组成的 emoji 序列保持不变
+        assert_eq!(
+            n.text("👨\u{200D}👩\u{200D}👧\u{200B}"),
+            "👨\u{200D}👩\u{200D}👧"
+        );
+        assert_eq!(n.text("輪盤賭"), "輪盤賭");
+        assert_eq!(n.clone().simplified(true).text("開始遊戲"), "开始游戏");
+        assert_eq!(n.nfkc(false).half_width(false).text("ｒｏｌｌ"), "ｒｏｌｌ");
+    }
+}
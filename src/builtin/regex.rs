@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+use serde::de::{self, value::MapDeserializer, DeserializeOwned, IntoDeserializer, Visitor};
+use walle_core::{prelude::async_trait, WalleError, WalleResult};
+
+use crate::utils::plain_text;
+use crate::{FromSessionPart, PreHandler, Rule, Session, Signal};
+
+pub struct RegexMatcher {
+    pub regex: Regex,
+}
+
+#[async_trait]
+impl Rule for RegexMatcher {
+    async fn rule(&self, session: &Session) -> Signal {
+        match plain_text(&session.event) {
+            Some(text) if self.regex.is_match(&text) => Signal::Matched,
+            _ => Signal::NotMatch,
+        }
+    }
+}
+
+#[async_trait]
+impl PreHandler for RegexMatcher {
+    async fn pre_handle(&self, session: &mut Session) -> Signal {
+        let Some(text) = plain_text(&session.event) else {
+            return Signal::NotMatch;
+        };
+        let Some(caps) = self.regex.captures(&text) else {
+            return Signal::NotMatch;
+        };
+        let captures = RegexCaptures {
+            groups: caps
+                .iter()
+                .map(|m| m.map(|m| m.as_str().to_owned()))
+                .collect(),
+            names: self
+                .regex
+                .capture_names()
+                .flatten()
+                .filter_map(|name| {
+                    caps.name(name)
+                        .map(|m| (name.to_owned(), m.as_str().to_owned()))
+                })
+                .collect(),
+        };
+        session.extensions.insert(captures);
+        Signal::Matched
+    }
+}
+
+impl RegexMatcher {
+    /// 编译正则，pattern 无效时返回错误，可同时作为 Rule 与 PreHandler 使用
+    pub fn new(pattern: &str) -> WalleResult<Self> {
+        Regex::new(pattern)
+            .map(|regex| Self { regex })
+            .map_err(|e| WalleError::Other(format!("invalid regex pattern {}: {}", pattern, e)))
+    }
+}
+
+/// 使用正则匹配 message 中的纯文本，并保存捕获组供 `RegexCaptures` 提取
+///
+/// pattern 无效时 panic，来自用户输入的 pattern 请使用 `RegexMatcher::new`
+pub fn regex(pattern: &str) -> impl PreHandler {
+    RegexMatcher::new(pattern).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// 使用正则匹配 message 中的纯文本
+///
+/// pattern 无效时 panic，来自用户输入的 pattern 请使用 `RegexMatcher::new`
+pub fn regex_rule(pattern: &str) -> impl Rule {
+    RegexMatcher::new(pattern).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// regex pre-handler 匹配到的捕获组
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RegexCaptures {
+    /// 位置捕获组，0 为整个匹配
+    pub groups: Vec<Option<String>>,
+    /// 命名捕获组
+    pub names: HashMap<String, String>,
+}
+
+impl RegexCaptures {
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.groups.get(index).and_then(|s| s.as_deref())
+    }
+    pub fn name(&self, name: &str) -> Option<&str> {
+        self.names.get(name).map(|s| s.as_str())
+    }
+    /// 将命名捕获组反序列化为结构体，字段值使用 `FromStr` 解析
+    pub fn deserialize<T: DeserializeOwned>(&self) -> WalleResult<T> {
+        T::deserialize(MapDeserializer::new(
+            self.names
+                .iter()
+                .map(|(k, v)| (k.as_str(), CaptureValue(v.as_str()))),
+        ))
+    }
+}
+
+#[async_trait]
+impl FromSessionPart for RegexCaptures {
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        session
+            .extensions
+            .get()
+            .ok_or_else(|| WalleError::Other("regex captures not found".to_owned()))
+    }
+}
+
+/// 将命名捕获组反序列化为 `T` 的提取器
+pub struct RegexArgs<T>(pub T);
+
+#[async_trait]
+impl<T: DeserializeOwned> FromSessionPart for RegexArgs<T> {
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        RegexCaptures::from_session_part(session)
+            .await?
+            .deserialize()
+            .map(Self)
+    }
+}
+
+struct CaptureValue<'a>(&'a str);
+
+impl<'de, 'a> IntoDeserializer<'de, WalleError> for CaptureValue<'a> {
+    type Deserializer = Self;
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+macro_rules! parse_value {
+    ($($f: ident => $v: ident),*) => {
+        $(fn $f<V: Visitor<'de>>(self, visitor: V) -> WalleResult<V::Value> {
+            visitor.$v(self.0.parse().map_err(|e| {
+                <WalleError as de::Error>::custom(format!("{}: {}", self.0, e))
+            })?)
+        })*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for CaptureValue<'a> {
+    type Error = WalleError;
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> WalleResult<V::Value> {
+        visitor.visit_str(self.0)
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> WalleResult<V::Value> {
+        visitor.visit_some(self)
+    }
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> WalleResult<V::Value> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+    parse_value!(
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64
+    );
+    serde::forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RegexCaptures, RegexMatcher};
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Roll {
+        count: u32,
+        sides: u32,
+        note: Option<String>,
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        assert!(RegexMatcher::new(r"roll (?P<count>\d+)").is_ok());
+        assert!(RegexMatcher::new("roll (").is_err());
+    }
+
+    #[test]
+    fn deserialize_named_captures() {
+        let captures = RegexCaptures {
+            groups: vec![
+                Some("roll 2d6".to_owned()),
+                Some("2".to_owned()),
+                Some("6".to_owned()),
+            ],
+            names: [("count", "2"), ("sides", "6")]
+                .into_iter()
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .collect(),
+        };
+        assert_eq!(captures.get(2), Some("6"));
+        assert_eq!(
+            captures.deserialize::<Roll>().unwrap(),
+            Roll {
+                count: 2,
+                sides: 6,
+                note: None
+            }
+        );
+    }
+}
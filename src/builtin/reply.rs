@@ -0,0 +1,139 @@
+use walle_core::{
+    action::Action,
+    prelude::async_trait,
+    segment::{MsgSegment, MsgSegmentRef, Segments},
+    util::{Value, ValueMapExt},
+    value_map, WalleError, WalleResult,
+};
+
+use crate::{
+    pre_handle_fn, rule_fn, ActionCaller, FromSessionPart, PreHandler, Rule, Session, Signal,
+};
+
+/// `reply_to_me` 移除的 reply 消息段，保存于 session extensions
+#[derive(Debug, Clone)]
+struct RemovedReply {
+    message_id: String,
+    user_id: String,
+}
+
+fn reply_to(seg: &Value) -> Option<(&str, &str)> {
+    match seg.try_as_ref::<MsgSegmentRef<'_>>() {
+        Ok(MsgSegmentRef::Reply {
+            message_id,
+            user_id,
+            ..
+        }) => Some((message_id, user_id)),
+        _ => None,
+    }
+}
+
+pub(crate) fn _reply_to_me_rule(session: &Session) -> Signal {
+    let self_id = session.event.selft().unwrap_or_default().user_id;
+    let Ok(segs) = session.event.extra.try_get_as_ref::<&Vec<Value>>("message") else {
+        return Signal::NotMatch;
+    };
+    if segs
+        .iter()
+        .filter_map(reply_to)
+        .any(|(_, user_id)| user_id == self_id)
+    {
+        Signal::Matched
+    } else {
+        Signal::NotMatch
+    }
+}
+
+pub(crate) fn _reply_to_me(session: &mut Session) -> Signal {
+    let self_id = session.event.selft().unwrap_or_default().user_id;
+    let Ok(segs) = session
+        .event
+        .extra
+        .try_get_as_mut::<&mut Vec<Value>>("message")
+    else {
+        return Signal::NotMatch;
+    };
+    let Some(index) = segs
+        .iter()
+        .position(|seg| reply_to(seg).is_some_and(|(_, user_id)| user_id == self_id))
+    else {
+        return Signal::NotMatch;
+    };
+    let reply = segs.remove(index);
+    if let Some((message_id, user_id)) = reply_to(&reply) {
+        session.extensions.insert(RemovedReply {
+            message_id: message_id.to_owned(),
+            user_id: user_id.to_owned(),
+        });
+    }
+    Signal::Matched
+}
+
+/// 消息回复了 bot 发送的消息，并移除该 reply 消息段
+///
+/// 被移除的消息段仍可通过 `QuotedMessage` 提取
+pub fn reply_to_me() -> impl PreHandler {
+    pre_handle_fn(_reply_to_me)
+}
+
+/// 消息回复了 bot 发送的消息
+pub fn reply_to_me_rule() -> impl Rule {
+    rule_fn(_reply_to_me_rule)
+}
+
+/// 消息所回复的消息
+#[derive(Debug, Clone)]
+pub struct QuotedMessage {
+    pub message_id: String,
+    pub user_id: String,
+    /// 被回复消息的内容，实现端不支持 `get_message` 时为 None
+    pub message: Option<Segments>,
+}
+
+async fn get_message(session: &Session, message_id: &str) -> WalleResult<Segments> {
+    let data = session
+        .call_action(Action {
+            action: "get_message".to_owned(),
+            params: value_map! { "message_id": message_id },
+            selft: None,
+        })
+        .await?
+        .as_result()
+        .map_err(WalleError::RespError)?;
+    let message = data
+        .downcast_map()?
+        .remove("message")
+        .ok_or_else(|| WalleError::MapMissedKey("message".to_owned()))?
+        .downcast_list()?;
+    message.into_iter().map(MsgSegment::try_from).collect()
+}
+
+#[async_trait]
+impl FromSessionPart for QuotedMessage {
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        let (message_id, user_id) = session
+            .event
+            .extra
+            .try_get_as_ref::<&Vec<Value>>("message")
+            .ok()
+            .and_then(|segs| segs.iter().find_map(reply_to))
+            .map(|(m, u)| (m.to_owned(), u.to_owned()))
+            .or_else(|| {
+                let removed = session.extensions.get::<RemovedReply>()?;
+                Some((removed.message_id, removed.user_id))
+            })
+            .ok_or_else(|| WalleError::Other("message has no reply segment".to_owned()))?;
+        let message = match get_message(session, &message_id).await {
+            Ok(message) => Some(message),
+            Err(e) => {
+                tracing::debug!(target: "Walle", "get quoted message failed: {}", e);
+                None
+            }
+        };
+        Ok(Self {
+            message_id,
+            user_id,
+            message,
+        })
+    }
+}
@@ -1,15 +1,16 @@
-use crate::{rule_fn, Rule, Session};
-use crate::{rule_fn_unwarp, Signal};
+use crate::{rule_fn, rule_fn_unwarp, Signal};
+use crate::{Rule, Session};
 use walle_core::segment::MsgSegmentRef;
 use walle_core::util::{Value, ValueMapExt};
-use walle_core::WalleResult;
+use walle_core::{prelude::async_trait, WalleResult};
 
 pub struct UserIdChecker {
     pub user_id: String,
 }
 
+#[async_trait]
 impl Rule for UserIdChecker {
-    fn rule(&self, session: &Session) -> Signal {
+    async fn rule(&self, session: &Session) -> Signal {
         if session
             .event
             .extra
@@ -37,8 +38,9 @@ pub struct GroupIdChecker {
     pub group_id: String,
 }
 
+#[async_trait]
 impl Rule for GroupIdChecker {
-    fn rule(&self, session: &Session) -> Signal {
+    async fn rule(&self, session: &Session) -> Signal {
         if session
             .event
             .extra
@@ -76,6 +78,10 @@ pub fn channel_id_check(guild_id: &str, channel_id: &str) -> impl Rule {
     })
 }
 
+/// 第一个消息段为以 `pat` 开头的 text 消息段
+///
+/// 与 `strip_prefix` 使用相同的文本，不忽略空白与 mention；
+/// 匹配所有 text 消息段拼接而成的文本请使用 `TextMatcher::new(TextMatchMode::StartWith, ..)`。
 pub fn start_with(pat: &str) -> impl Rule {
     let word = pat.to_string();
     rule_fn(move |session: &Session| {
@@ -97,8 +103,8 @@ pub fn start_with(pat: &str) -> impl Rule {
 
 fn _mention_me(session: &Session) -> WalleResult<Signal> {
     let alt = &session.event.extra.try_get_as_ref::<&str>("alt_message")?;
-    for nickname in &session.config.nicknames {
-        if alt.starts_with(nickname) {
+    for nickname in &session.nicknames() {
+        if alt.starts_with(nickname.as_str()) {
             return Ok(Signal::Matched);
         }
     }
@@ -128,7 +134,58 @@ pub fn to_me_rule() -> impl Rule {
         if session.event.detail_type.as_str() == "private" {
             Ok(Signal::Matched)
         } else {
-            _mention_me(session)
+            let mentioned = _mention_me(session).unwrap_or_default();
+            Ok(mentioned | super::reply::_reply_to_me_rule(session))
         }
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::{start_with, to_me_rule};
+    use crate::builtin::strip_prefix;
+    use crate::matcher::mock::{
+        group_message, mention_seg, session, text_seg, with_message, MockCaller,
+    };
+    use crate::{PreHandler, Rule, Signal};
+    use std::sync::Arc;
+    use walle_core::{util::Value, value_map};
+
+    #[tokio::test]
+    async fn reply_to_me_without_alt_message() {
+        let mut event = group_message("user", "hi");
+        event.extra.remove("alt_message");
+        if let Some(Value::List(segs)) = event.extra.get_mut("message") {
+            segs.insert(
+                0,
+                Value::Map(value_map! {
+                    "type": "reply",
+                    "data": value_map! { "message_id": "0", "user_id": "bot" }
+                }),
+            );
+        }
+        let s = session(event, Arc::new(MockCaller::default()), Default::default());
+        assert_eq!(to_me_rule().rule(&s).await, Signal::Matched);
+        let s = session(
+            group_message("user", "hi"),
+            Arc::new(MockCaller::default()),
+            Default::default(),
+        );
+        assert_eq!(to_me_rule().rule(&s).await, Signal::NotMatch);
+    }
+
+    #[tokio::test]
+    async fn start_with_strip_prefix() {
+        let caller = Arc::new(MockCaller::default());
+        for (segs, expected) in [
+            (vec![text_seg("xyz")], Signal::Matched),
+            (vec![text_seg(" xyz")], Signal::NotMatch),
+            (vec![mention_seg("bot"), text_seg("xyz")], Signal::NotMatch),
+        ] {
+            let event = with_message(group_message("user", ""), segs);
+            let mut s = session(event, caller.clone(), Default::default());
+            assert_eq!(start_with("x").rule(&s).await, expected);
+            assert_eq!(strip_prefix("x").pre_handle(&mut s).await, expected);
+        }
+    }
+}
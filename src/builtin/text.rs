@@ -0,0 +1,237 @@
+use walle_core::{prelude::async_trait, WalleError, WalleResult};
+
+use crate::utils::{fold_width, message_text};
+use crate::{rule_fn, FromSessionPart, PreHandler, Rule, Session, Signal};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextMatchMode {
+    /// 完全匹配
+    Full,
+    StartWith,
+    EndWith,
+    /// 包含任一关键词
+    Contains,
+}
+
+/// 文本匹配器
+///
+/// 匹配 message 中所有 text 消息段拼接而成的文本（首尾空白会被忽略），
+/// 作为 PreHandler 使用时会保存匹配到的关键词供 `Keyword` 提取。
+#[derive(Debug, Clone)]
+pub struct TextMatcher {
+    pub mode: TextMatchMode,
+    pub keywords: Vec<String>,
+    pub ignore_case: bool,
+    pub ignore_width: bool,
+    pub ignore_mentions: bool,
+}
+
+impl TextMatcher {
+    pub fn new<I, S>(mode: TextMatchMode, keywords: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: ToString,
+    {
+        Self {
+            mode,
+            keywords: keywords.into_iter().map(|s| s.to_string()).collect(),
+            ignore_case: false,
+            ignore_width: false,
+            ignore_mentions: false,
+        }
+    }
+
+    /// 忽略大小写
+    pub fn ignore_case(self) -> Self {
+        Self {
+            ignore_case: true,
+            ..self
+        }
+    }
+
+    /// 忽略全角半角差异
+    pub fn ignore_width(self) -> Self {
+        Self {
+            ignore_width: true,
+            ..self
+        }
+    }
+
+    /// 忽略 mention 消息段，否则 mention 将以 `@user_id` 参与匹配
+    pub fn ignore_mentions(self) -> Self {
+        Self {
+            ignore_mentions: true,
+            ..self
+        }
+    }
+
+    fn normalize(&self, s: &str) -> String {
+        let s = if self.ignore_width {
+            fold_width(s)
+        } else {
+            s.to_owned()
+        };
+        if self.ignore_case {
+            s.to_lowercase()
+        } else {
+            s
+        }
+    }
+
+    /// 返回匹配到的关键词
+    pub fn matches(&self, session: &Session) -> Option<&str> {
+        let text = message_text(&session.event, self.ignore_mentions)?;
+        let text = self.normalize(text.trim());
+        self.keywords
+            .iter()
+            .find(|keyword| {
+                let keyword = self.normalize(keyword);
+                match self.mode {
+                    TextMatchMode::Full => text == keyword,
+                    TextMatchMode::StartWith => text.starts_with(&keyword),
+                    TextMatchMode::EndWith => text.ends_with(&keyword),
+                    TextMatchMode::Contains => text.contains(&keyword),
+                }
+            })
+            .map(|s| s.as_str())
+    }
+
+    /// 转换为不保存关键词的 Rule
+    pub fn rule(self) -> impl Rule {
+        rule_fn(move |session: &Session| match self.matches(session) {
+            Some(_) => Signal::Matched,
+            None => Signal::NotMatch,
+        })
+    }
+}
+
+#[async_trait]
+impl PreHandler for TextMatcher {
+    async fn pre_handle(&self, session: &mut Session) -> Signal {
+        let Some(keyword) = self.matches(session).map(ToOwned::to_owned) else {
+            return Signal::NotMatch;
+        };
+        session.extensions.insert(Keyword(keyword));
+        Signal::Matched
+    }
+}
+
+/// 消息文本与 `pat` 完全一致
+pub fn fullmatch(pat: &str) -> TextMatcher {
+    TextMatcher::new(TextMatchMode::Full, [pat])
+}
+
+/// 消息文本包含 `pat`
+pub fn contains(pat: &str) -> TextMatcher {
+    TextMatcher::new(TextMatchMode::Contains, [pat])
+}
+
+/// 消息文本以 `pat` 结尾
+pub fn end_with(pat: &str) -> TextMatcher {
+    TextMatcher::new(TextMatchMode::EndWith, [pat])
+}
+
+/// 消息文本包含任一关键词
+pub fn keywords<I, S>(keywords: I) -> TextMatcher
+where
+    I: IntoIterator<Item = S>,
+    S: ToString,
+{
+    TextMatcher::new(TextMatchMode::Contains, keywords)
+}
+
+/// 文本匹配 pre-handler 匹配到的关键词
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keyword(pub String);
+
+#[async_trait]
+impl FromSessionPart for Keyword {
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        session
+            .extensions
+            .get()
+            .ok_or_else(|| WalleError::Other("matched keyword not found".to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{contains, end_with, fullmatch, keywords, Keyword, TextMatchMode, TextMatcher};
+    use crate::matcher::mock::{
+        group_message, mention_seg, session, text_seg, with_message, MockCaller,
+    };
+    use crate::{FromSessionPart, PreHandler, Session, Signal};
+    use std::sync::Arc;
+    use walle_core::util::Value;
+
+    fn message(segs: Vec<Value>) -> Session {
+        let event = with_message(group_message("user", ""), segs);
+        session(event, Arc::new(MockCaller::default()), Default::default())
+    }
+
+    fn text(text: &str) -> Session {
+        message(vec![text_seg(text)])
+    }
+
+    #[test]
+    fn modes() {
+        let s = text("  hello world ");
+        assert_eq!(fullmatch("hello world").matches(&s), Some("hello world"));
+        assert_eq!(fullmatch("hello").matches(&s), None);
+        let start = TextMatcher::new(TextMatchMode::StartWith, ["hello"]);
+        assert_eq!(start.matches(&s), Some("hello"));
+        assert_eq!(end_with("world").matches(&s), Some("world"));
+        assert_eq!(end_with("hello").matches(&s), None);
+        assert_eq!(contains("o w").matches(&s), Some("o w"));
+        assert_eq!(keywords(["bye", "world"]).matches(&s), Some("world"));
+        assert_eq!(keywords(["bye"]).matches(&s), None);
+    }
+
+    #[test]
+    fn folding() {
+        let s = text("ＨＥＬＬＯ　Walle");
+        assert_eq!(fullmatch("hello walle").matches(&s), None);
+        assert_eq!(fullmatch("hello walle").ignore_case().matches(&s), None);
+        assert_eq!(
+            fullmatch("HELLO Walle").ignore_width().matches(&s),
+            Some("HELLO Walle")
+        );
+        let matcher = fullmatch("hello walle").ignore_case().ignore_width();
+        assert_eq!(matcher.matches(&s), Some("hello walle"));
+    }
+
+    #[test]
+    fn mentions() {
+        let s = message(vec![
+            text_seg("hi "),
+            mention_seg("bot"),
+            text_seg(" there"),
+        ]);
+        assert_eq!(
+            fullmatch("hi @bot there").matches(&s),
+            Some("hi @bot there")
+        );
+        assert_eq!(fullmatch("hi  there").matches(&s), None);
+        let matcher = fullmatch("hi  there").ignore_mentions();
+        assert_eq!(matcher.matches(&s), Some("hi  there"));
+    }
+
+    #[tokio::test]
+    async fn keyword() {
+        let mut s = text("I like walle");
+        assert_eq!(
+            keywords(["rust", "walle"]).pre_handle(&mut s).await,
+            Signal::Matched
+        );
+        assert_eq!(
+            Keyword::from_session_part(&mut s).await.unwrap(),
+            Keyword("walle".to_owned())
+        );
+        let mut s = text("nothing");
+        assert_eq!(
+            keywords(["walle"]).pre_handle(&mut s).await,
+            Signal::NotMatch
+        );
+        assert!(Keyword::from_session_part(&mut s).await.is_err());
+    }
+}
@@ -143,6 +143,7 @@ macro_rules! action_ext {
         }
     };
     ($fname: ident, $a: expr => $rty: ty, $($f: ident: $fty: ty),*) => {
+        #[allow(clippy::too_many_arguments)]
         fn $fname<'a, 't>(&'a self, $($f: $fty),*) -> Pin<Box<dyn Future<Output = WalleResult<$rty>> + Send + 't>>
         where
             'a: 't,
@@ -1,9 +1,657 @@
-use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
+use time::UtcOffset;
+use tokio::sync::{Mutex, RwLock};
 pub use walle_core::config::*;
 
+use crate::builtin::Normalize;
+use walle_core::{event::Event, util::ValueMapExt, WalleError, WalleResult};
+
 /// Matchers 可配置项
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct MatchersConfig {
     #[serde(default = "Vec::default")]
     pub nicknames: Vec<String>,
+    /// 将 `get_self_info` 获取的 bot 名称作为该 bot 的昵称
+    #[serde(default)]
+    pub self_info_nicknames: bool,
+    /// 将 bot 在群组中的群名片作为该群中的昵称
+    #[serde(default)]
+    pub group_card_nicknames: bool,
+    /// 超级用户 user_id，拥有所有群组权限
+    #[serde(default = "Vec::default")]
+    pub superusers: Vec<String>,
+    /// 全局黑白名单
+    #[serde(default)]
+    pub access: AccessConfig,
+    /// 以 bot user_id 为键的黑白名单，与全局黑白名单同时生效
+    #[serde(default)]
+    pub bot_access: HashMap<String, AccessConfig>,
+    /// 时间相关 Rule 与日志使用的时区，如 `"+08:00"`
+    #[serde(
+        default = "default_utc_offset",
+        serialize_with = "serialize_utc_offset",
+        deserialize_with = "deserialize_utc_offset"
+    )]
+    pub utc_offset: UtcOffset,
+    /// 命令前缀，空字符串表示无需前缀
+    #[serde(default = "default_command_start")]
+    pub command_start: Vec<String>,
+    /// 以群组 group_id 或 `guild_id:channel_id` 为键的命令前缀，覆盖 `command_start`
+    #[serde(default)]
+    pub group_command_start: HashMap<String, Vec<String>>,
+    /// 命令与子命令间的分隔符，如 `"."` 使 `admin.ban` 等同于 `admin ban`
+    #[serde(default)]
+    pub command_sep: Vec<String>,
+    /// 在所有 matcher 之前对消息文本进行规范化
+    #[serde(default)]
+    pub normalize: Option<Normalize>,
+    /// 消息以命令前缀开头但未匹配任何命令时提示相近的命令
+    ///
+    /// 仅对非空命令前缀生效，`command_start` 只有空字符串（默认值）时不会提示。
+    #[serde(default)]
+    pub suggest: Option<SuggestConfig>,
+    /// 在所有处理之前丢弃重复、过期与 bot 自身发出的 event
+    #[serde(default)]
+    pub filter: FilterConfig,
+    /// 多个 bot 位于同一群组时只由一个 bot 响应同一 event
+    #[serde(default)]
+    pub arbitration: Option<ArbitrationConfig>,
+    /// handler 返回错误时是否将错误信息回复给用户
+    #[serde(default)]
+    pub reply_errors: bool,
+    /// 命令参数有误且没有 matcher 处理该 event 时的回复，`{usage}` 替换为命令用法，为空时不回复
+    #[serde(default = "default_usage_reply")]
+    pub usage_reply: String,
+    /// `ExecutionPolicy::SingleFlightUser` 的 handler 仍在执行时的回复，为空时不回复
+    #[serde(default = "default_busy_reply")]
+    pub busy_reply: String,
+    /// 以插件名为键的插件配置，见 `PluginSection`
+    #[serde(default)]
+    pub plugins: HashMap<String, toml::Value>,
+}
+
+impl Default for MatchersConfig {
+    fn default() -> Self {
+        Self {
+            nicknames: Vec::default(),
+            self_info_nicknames: false,
+            group_card_nicknames: false,
+            superusers: Vec::default(),
+            access: AccessConfig::default(),
+            bot_access: HashMap::default(),
+            utc_offset: default_utc_offset(),
+            command_start: default_command_start(),
+            group_command_start: HashMap::default(),
+            command_sep: Vec::default(),
+            normalize: None,
+            suggest: None,
+            filter: FilterConfig::default(),
+            arbitration: None,
+            reply_errors: false,
+            usage_reply: default_usage_reply(),
+            busy_reply: default_busy_reply(),
+            plugins: HashMap::default(),
+        }
+    }
+}
+
+fn default_usage_reply() -> String {
+    "参数有误\n{usage}".to_owned()
+}
+
+fn default_busy_reply() -> String {
+    "请等待上一个请求处理完毕".to_owned()
+}
+
+fn default_command_start() -> Vec<String> {
+    vec![String::default()]
+}
+
+fn default_utc_offset() -> UtcOffset {
+    UtcOffset::from_whole_seconds(8 * 3600).unwrap()
+}
+
+/// 解析 `+08:00`、`-05:30`、`+8` 格式的 UTC 偏移
+pub fn parse_utc_offset(s: &str) -> Option<UtcOffset> {
+    let (sign, s) = match s.as_bytes().first()? {
+        b'+' => (1, &s[1..]),
+        b'-' => (-1, &s[1..]),
+        _ => (1, s),
+    };
+    let (hours, minutes) = s.split_once(':').unwrap_or((s, "0"));
+    let hours: i8 = hours.parse().ok()?;
+    let minutes: i8 = minutes.parse().ok()?;
+    UtcOffset::from_hms(sign * hours, sign * minutes, 0).ok()
+}
+
+fn serialize_utc_offset<S: Serializer>(offset: &UtcOffset, s: S) -> Result<S::Ok, S::Error> {
+    let (hours, minutes, _) = offset.as_hms();
+    let sign = if offset.is_negative() { '-' } else { '+' };
+    s.serialize_str(&format!("{}{:02}:{:02}", sign, hours.abs(), minutes.abs()))
+}
+
+fn deserialize_utc_offset<'de, D: Deserializer<'de>>(d: D) -> Result<UtcOffset, D::Error> {
+    let s = String::deserialize(d)?;
+    parse_utc_offset(&s)
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid utc offset: {}", s)))
+}
+
+impl MatchersConfig {
+    /// 从 toml 文件读取配置
+    pub fn load<P: Into<PathBuf>>(path: P) -> WalleResult<Self> {
+        let data = std::fs::read_to_string(path.into())?;
+        toml::from_str(&data).map_err(|e| WalleError::Other(e.to_string()))
+    }
+
+    /// 将配置保存为 toml 文件
+    pub fn save<P: Into<PathBuf>>(&self, path: P) -> WalleResult<()> {
+        let data = toml::Value::try_from(self)
+            .and_then(|v| toml::to_string_pretty(&v))
+            .map_err(|e| WalleError::Other(e.to_string()))?;
+        std::fs::write(path.into(), data)?;
+        Ok(())
+    }
+
+    /// 读取并校验插件配置，缺失时使用默认值
+    pub fn plugin<T: PluginSection>(&self) -> WalleResult<T> {
+        let section = match self.plugins.get(T::NAME) {
+            Some(value) => value
+                .clone()
+                .try_into::<T>()
+                .map_err(|e| WalleError::Other(format!("plugin {} config: {}", T::NAME, e)))?,
+            None => T::default(),
+        };
+        section
+            .validate()
+            .map_err(|e| WalleError::Other(format!("plugin {} config: {}", T::NAME, e)))?;
+        Ok(section)
+    }
+
+    /// event 所在群组或频道适用的命令前缀
+    pub fn command_start_for(&self, event: &Event) -> &[String] {
+        let extra = &event.extra;
+        let key = extra.get_downcast::<String>("group_id").ok().or_else(|| {
+            let guild_id = extra.try_get_as_ref::<&str>("guild_id").ok()?;
+            let channel_id = extra.try_get_as_ref::<&str>("channel_id").ok()?;
+            Some(format!("{}:{}", guild_id, channel_id))
+        });
+        key.and_then(|key| self.group_command_start.get(&key))
+            .unwrap_or(&self.command_start)
+    }
+
+    /// 获取全局或指定 bot 的黑白名单
+    pub fn access_mut(&mut self, bot_id: Option<&str>) -> &mut AccessConfig {
+        match bot_id {
+            Some(bot_id) => self.bot_access.entry(bot_id.to_owned()).or_default(),
+            None => &mut self.access,
+        }
+    }
+
+    /// event 是否通过黑白名单，超级用户总是通过
+    pub fn access_allowed(&self, event: &Event) -> bool {
+        let extra = &event.extra;
+        let user_id = extra.try_get_as_ref::<&str>("user_id").ok();
+        if user_id.is_some_and(|id| self.superusers.iter().any(|s| s == id)) {
+            return true;
+        }
+        let target = AccessTarget {
+            user_id,
+            group_id: extra.try_get_as_ref::<&str>("group_id").ok(),
+            channel: extra
+                .try_get_as_ref::<&str>("guild_id")
+                .ok()
+                .zip(extra.try_get_as_ref::<&str>("channel_id").ok()),
+        };
+        self.access.allowed(&target)
+            && event
+                .self_id()
+                .and_then(|id| self.bot_access.get(&id))
+                .is_none_or(|access| access.allowed(&target))
+    }
+}
+
+/// 插件配置段，对应配置文件中的 `[plugins.<NAME>]`
+///
+/// 通过 `Matchers::plugin_config` 注册后会在启动时校验，
+/// 通过 `PluginConfig<T>` extractor 获取当前配置。
+pub trait PluginSection: DeserializeOwned + Default + Send + Sync + 'static {
+    const NAME: &'static str;
+    /// 校验配置值
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// 命令提示配置
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SuggestConfig {
+    /// 允许的最大编辑距离
+    #[serde(default = "default_max_distance")]
+    pub max_distance: usize,
+    /// 同一用户两次提示的最小间隔，单位为秒
+    #[serde(default = "default_suggest_interval")]
+    pub interval: u64,
+    /// 提示消息，`{word}` 替换为用户输入的命令，`{command}` 替换为带前缀的相近命令
+    #[serde(default = "default_suggest_message")]
+    pub message: String,
+}
+
+impl Default for SuggestConfig {
+    fn default() -> Self {
+        Self {
+            max_distance: default_max_distance(),
+            interval: default_suggest_interval(),
+            message: default_suggest_message(),
+        }
+    }
+}
+
+/// event 过滤配置，默认不过滤任何 event
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct FilterConfig {
+    /// 同一 bot 收到相同 event id 视为重复的时间窗口，单位为秒，0 表示不去重
+    #[serde(default)]
+    pub dedup_window: u64,
+    /// 按 `event.time` 丢弃早于该秒数的 event，0 表示不限制
+    #[serde(default)]
+    pub max_age: u64,
+    /// 丢弃 `user_id` 与 bot 自身相同的 event
+    #[serde(default)]
+    pub ignore_self: bool,
+}
+
+/// 响应 bot 的选举策略
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ElectionPolicy {
+    /// 按 `priority` 中的顺序选择，未列出的 bot 排在最后
+    #[default]
+    Priority,
+    /// 选择最先送达 event 的 bot
+    LowestLatency,
+    /// 每个群组或频道中依次轮换
+    RoundRobin,
+}
+
+/// 多 bot 仲裁配置
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArbitrationConfig {
+    #[serde(default)]
+    pub policy: ElectionPolicy,
+    /// bot user_id 优先级列表
+    #[serde(default)]
+    pub priority: Vec<String>,
+    /// 收集其他 bot 同一 event 的等待时间，单位为毫秒
+    ///
+    /// 需要选举时 event 将延迟该时长才被处理，群组中近期只有一个 bot 时不会延迟。
+    #[serde(default = "default_arbitration_window")]
+    pub window: u64,
+}
+
+impl Default for ArbitrationConfig {
+    fn default() -> Self {
+        Self {
+            policy: ElectionPolicy::default(),
+            priority: Vec::default(),
+            window: default_arbitration_window(),
+        }
+    }
+}
+
+fn default_arbitration_window() -> u64 {
+    500
+}
+
+fn default_max_distance() -> usize {
+    2
+}
+
+fn default_suggest_message() -> String {
+    "未知命令 {word}，你是否想使用 {command}？".to_owned()
+}
+
+fn default_suggest_interval() -> u64 {
+    60
+}
+
+/// 黑白名单配置
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AccessConfig {
+    /// 名单内的 event 将被忽略
+    #[serde(default)]
+    pub blocklist: AccessList,
+    /// 非空的名单项将只允许名单内的 event 通过
+    #[serde(default)]
+    pub allowlist: AccessList,
+}
+
+struct AccessTarget<'a> {
+    user_id: Option<&'a str>,
+    group_id: Option<&'a str>,
+    channel: Option<(&'a str, &'a str)>,
+}
+
+impl AccessConfig {
+    fn allowed(&self, target: &AccessTarget<'_>) -> bool {
+        let block = &self.blocklist;
+        let allow = &self.allowlist;
+        let blocked = target.user_id.is_some_and(|id| block.has_user(id))
+            || target.group_id.is_some_and(|id| block.has_group(id))
+            || target.channel.is_some_and(|(g, c)| block.has_channel(g, c));
+        let not_allowed = target
+            .user_id
+            .is_some_and(|id| !allow.users.is_empty() && !allow.has_user(id))
+            || target
+                .group_id
+                .is_some_and(|id| !allow.groups.is_empty() && !allow.has_group(id))
+            || target
+                .channel
+                .is_some_and(|(g, c)| !allow.channels.is_empty() && !allow.has_channel(g, c));
+        !(blocked || not_allowed)
+    }
+}
+
+/// user、group 与 channel 名单
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct AccessList {
+    #[serde(default)]
+    pub users: Vec<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub channels: Vec<ChannelRef>,
+}
+
+/// guild_id 与 channel_id 组合
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ChannelRef {
+    pub guild_id: String,
+    pub channel_id: String,
+}
+
+fn add(list: &mut Vec<String>, id: String) -> bool {
+    if list.contains(&id) {
+        false
+    } else {
+        list.push(id);
+        true
+    }
+}
+
+fn remove(list: &mut Vec<String>, id: &str) -> bool {
+    let len = list.len();
+    list.retain(|i| i != id);
+    list.len() != len
+}
+
+impl AccessList {
+    pub fn has_user(&self, user_id: &str) -> bool {
+        self.users.iter().any(|i| i == user_id)
+    }
+    pub fn has_group(&self, group_id: &str) -> bool {
+        self.groups.iter().any(|i| i == group_id)
+    }
+    pub fn has_channel(&self, guild_id: &str, channel_id: &str) -> bool {
+        self.channels
+            .iter()
+            .any(|c| c.guild_id == guild_id && c.channel_id == channel_id)
+    }
+    /// 添加用户，已存在时返回 false
+    pub fn add_user<S: ToString>(&mut self, user_id: S) -> bool {
+        add(&mut self.users, user_id.to_string())
+    }
+    /// 移除用户，不存在时返回 false
+    pub fn remove_user(&mut self, user_id: &str) -> bool {
+        remove(&mut self.users, user_id)
+    }
+    pub fn add_group<S: ToString>(&mut self, group_id: S) -> bool {
+        add(&mut self.groups, group_id.to_string())
+    }
+    pub fn remove_group(&mut self, group_id: &str) -> bool {
+        remove(&mut self.groups, group_id)
+    }
+    pub fn add_channel<S: ToString>(&mut self, guild_id: S, channel_id: S) -> bool {
+        let channel = ChannelRef {
+            guild_id: guild_id.to_string(),
+            channel_id: channel_id.to_string(),
+        };
+        if self.channels.contains(&channel) {
+            false
+        } else {
+            self.channels.push(channel);
+            true
+        }
+    }
+    pub fn remove_channel(&mut self, guild_id: &str, channel_id: &str) -> bool {
+        let len = self.channels.len();
+        self.channels
+            .retain(|c| !(c.guild_id == guild_id && c.channel_id == channel_id));
+        self.channels.len() != len
+    }
+}
+
+type ConfigValidator = Box<dyn Fn(&MatchersConfig) -> WalleResult<()> + Send + Sync + 'static>;
+
+/// `Matchers::plugin_config` 注册的校验函数
+#[derive(Default)]
+struct Validators(std::sync::RwLock<Vec<ConfigValidator>>);
+
+impl std::fmt::Debug for Validators {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Validators").finish_non_exhaustive()
+    }
+}
+
+/// 运行时可修改的 MatchersConfig
+///
+/// 设置了保存路径时，每次修改都会写回文件
+#[derive(Debug, Default)]
+pub struct ConfigStore {
+    config: RwLock<Arc<MatchersConfig>>,
+    path: Option<PathBuf>,
+    validators: Validators,
+    /// 串行化 `update`，写文件期间不持有 `config` 的锁
+    updating: Mutex<()>,
+}
+
+impl ConfigStore {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        Self {
+            path,
+            ..Default::default()
+        }
+    }
+
+    /// 使用新的保存路径，保留已注册的校验函数
+    pub(crate) fn with_path(&self, path: PathBuf) -> Self {
+        let validators = std::mem::take(&mut *self.validators.0.write().unwrap());
+        Self {
+            path: Some(path),
+            validators: Validators(std::sync::RwLock::new(validators)),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn add_validator(&self, validator: ConfigValidator) {
+        self.validators.0.write().unwrap().push(validator);
+    }
+
+    /// 使用所有已注册的校验函数校验配置
+    pub fn validate(&self, config: &MatchersConfig) -> WalleResult<()> {
+        let errors: Vec<String> = self
+            .validators
+            .0
+            .read()
+            .unwrap()
+            .iter()
+            .filter_map(|validate| validate(config).err())
+            .map(|e| e.to_string())
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(WalleError::Other(errors.join("\n")))
+        }
+    }
+
+    /// 从保存路径读取配置，不会替换当前配置
+    pub fn load(&self) -> WalleResult<MatchersConfig> {
+        match &self.path {
+            Some(path) => MatchersConfig::load(path),
+            None => Err(WalleError::Other("config path not set".to_owned())),
+        }
+    }
+
+    /// 获取当前配置
+    pub async fn get(&self) -> Arc<MatchersConfig> {
+        self.config.read().await.clone()
+    }
+
+    /// 替换当前配置，不会写回文件
+    pub async fn set(&self, config: MatchersConfig) {
+        crate::utils::set_log_utc_offset(config.utc_offset);
+        *self.config.write().await = Arc::new(config);
+    }
+
+    /// 修改当前配置，校验通过后写回文件并替换当前配置
+    ///
+    /// 写文件时不阻塞 `get`，校验或写入失败时当前配置不变。
+    pub async fn update<F>(&self, f: F) -> WalleResult<()>
+    where
+        F: FnOnce(&mut MatchersConfig),
+    {
+        let _updating = self.updating.lock().await;
+        let mut new = self.get().await.as_ref().clone();
+        f(&mut new);
+        self.validate(&new)?;
+        if let Some(path) = self.path.clone() {
+            let config = new.clone();
+            tokio::task::spawn_blocking(move || config.save(path))
+                .await
+                .map_err(|e| WalleError::Other(e.to_string()))??;
+        }
+        self.set(new).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MatchersConfig, PluginSection};
+    use serde::Deserialize;
+    use walle_core::{event::Event, value_map};
+
+    fn group_event(user_id: &str, group_id: &str) -> Event {
+        Event {
+            id: String::default(),
+            time: 0.0,
+            ty: "message".to_owned(),
+            detail_type: "group".to_owned(),
+            sub_type: String::default(),
+            extra: value_map! {
+                "self": { "platform": "qq", "user_id": "10000" },
+                "user_id": user_id,
+                "group_id": group_id,
+            },
+        }
+    }
+
+    #[test]
+    fn access_lists() {
+        let mut config = MatchersConfig {
+            superusers: vec!["1".to_owned()],
+            ..Default::default()
+        };
+        config.access_mut(None).blocklist.add_user("2");
+        config.access_mut(Some("10000")).allowlist.add_group("100");
+        assert!(config.access_allowed(&group_event("1", "200")));
+        assert!(!config.access_allowed(&group_event("2", "100")));
+        assert!(!config.access_allowed(&group_event("3", "200")));
+        assert!(config.access_allowed(&group_event("3", "100")));
+
+        let path = crate::matcher::mock::temp_path("access_lists.toml");
+        config.save(&path).unwrap();
+        let loaded = MatchersConfig::load(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(!loaded.access_allowed(&group_event("3", "200")));
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(default)]
+    struct Roulette {
+        bullets: u8,
+        timeout: u64,
+    }
+
+    impl Default for Roulette {
+        fn default() -> Self {
+            Self {
+                bullets: 6,
+                timeout: 60,
+            }
+        }
+    }
+
+    impl PluginSection for Roulette {
+        const NAME: &'static str = "roulette";
+        fn validate(&self) -> Result<(), String> {
+            if self.bullets == 0 {
+                return Err("bullets must be positive".to_owned());
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn plugin_sections() {
+        let config = MatchersConfig::default();
+        assert_eq!(config.plugin::<Roulette>().unwrap(), Roulette::default());
+        let config: MatchersConfig = toml::from_str("[plugins.roulette]\nbullets = 8").unwrap();
+        assert_eq!(config.plugin::<Roulette>().unwrap().bullets, 8);
+        assert_eq!(config.plugin::<Roulette>().unwrap().timeout, 60);
+        let config: MatchersConfig = toml::from_str("[plugins.roulette]\nbullets = 0").unwrap();
+        assert!(config.plugin::<Roulette>().is_err());
+    }
+
+    #[tokio::test]
+    async fn update_access_lists() {
+        use crate::Matchers;
+
+        let path = crate::matcher::mock::temp_path("update.toml");
+        let matchers = Matchers::default()
+            .plugin_config::<Roulette>()
+            .config_path(&path);
+        matchers.block_user("2").await.unwrap();
+        matchers.allow_group("100").await.unwrap();
+        let config = matchers.config.get().await;
+        assert!(config.access.blocklist.has_user("2"));
+        assert!(config.access.allowlist.has_group("100"));
+        let saved = |id| {
+            MatchersConfig::load(&path)
+                .unwrap()
+                .access
+                .blocklist
+                .has_user(id)
+        };
+        assert!(saved("2"));
+
+        let invalid = matchers
+            .config
+            .update(|config| {
+                config.plugins.insert(
+                    "roulette".to_owned(),
+                    toml::from_str("bullets = 0").unwrap(),
+                );
+                config.access.blocklist.add_user("3");
+            })
+            .await;
+        assert!(invalid.is_err());
+        assert!(!matchers.config.get().await.access.blocklist.has_user("3"));
+        assert!(!saved("3"));
+        matchers.unblock_user("2").await.unwrap();
+        assert!(!matchers.config.get().await.access.blocklist.has_user("2"));
+        std::fs::remove_file(&path).ok();
+    }
 }
@@ -24,24 +24,20 @@ pub use tokio;
 #[doc(hidden)]
 pub use tracing;
 pub use walle_core;
+pub use walle_macros::{handler, plugin};
 
 pub mod builtin;
 
 pub type Walle = Arc<OneBot<AppOBC<Action, Resp>, Matchers>>;
 
 /// 构造一个新的 Walle 实例
+///
+/// 日志时间使用 `MatchersConfig::utc_offset` 时区，启动前默认为 UTC+8
 pub fn new_walle(matchers: Matchers, env: &str) -> Walle {
-    let timer = tracing_subscriber::fmt::time::OffsetTime::new(
-        time::UtcOffset::from_hms(8, 0, 0).unwrap(),
-        time::format_description::parse(
-            "[year repr:last_two]-[month]-[day] [hour]:[minute]:[second]",
-        )
-        .unwrap(),
-    );
     let env = EnvFilter::from(env);
     tracing_subscriber::fmt()
         .with_env_filter(env)
-        .with_timer(timer)
+        .with_timer(utils::LogTimer)
         .init();
     Arc::new(walle_core::OneBot::new(AppOBC::new(), matchers))
 }
@@ -49,18 +45,11 @@ pub fn new_walle(matchers: Matchers, env: &str) -> Walle {
 pub fn test_walle(
     matchers: Matchers,
 ) -> Arc<OneBot<walle_core::alt::TracingHandler<Event, Action, Resp>, Matchers>> {
-    let timer = tracing_subscriber::fmt::time::OffsetTime::new(
-        time::UtcOffset::from_hms(8, 0, 0).unwrap(),
-        time::format_description::parse(
-            "[year repr:last_two]-[month]-[day] [hour]:[minute]:[second]",
-        )
-        .unwrap(),
-    );
     let env = tracing_subscriber::EnvFilter::from("debug");
 
     tracing_subscriber::fmt()
         .with_env_filter(env)
-        .with_timer(timer)
+        .with_timer(utils::LogTimer)
         .init();
     Arc::new(walle_core::OneBot::new(
         walle_core::alt::TracingHandler::default(),
@@ -0,0 +1,300 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use dashmap::{mapref::entry::Entry, DashMap};
+use tokio::sync::watch;
+use walle_core::{event::Event, structs::Selft, util::ValueMapExt};
+
+use crate::{utils::message_text, ArbitrationConfig, ElectionPolicy};
+
+/// 同一群组或频道中由不同 bot 收到的同一 event
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct EventKey {
+    platform: String,
+    chat: String,
+    user_id: String,
+    content: String,
+}
+
+impl EventKey {
+    fn new(event: &Event, selft: &Selft) -> Option<Self> {
+        let extra = &event.extra;
+        let chat = extra.get_downcast::<String>("group_id").ok().or_else(|| {
+            let guild_id = extra.get_downcast::<String>("guild_id").ok()?;
+            let channel_id = extra.get_downcast::<String>("channel_id").ok()?;
+            Some(format!("{}:{}", guild_id, channel_id))
+        })?;
+        Some(Self {
+            platform: selft.platform.clone(),
+            chat,
+            user_id: extra.get_downcast("user_id").unwrap_or_default(),
+            content: message_text(event, false)
+                .unwrap_or_else(|| format!("{}.{}", event.detail_type, event.time)),
+        })
+    }
+
+    fn chat_key(&self) -> (String, String) {
+        (self.platform.clone(), self.chat.clone())
+    }
+}
+
+#[derive(Debug)]
+struct Election {
+    start: Instant,
+    candidates: Vec<Selft>,
+    /// 选举结果，后到达的 bot 订阅并等待
+    winner: watch::Sender<Option<Selft>>,
+}
+
+/// 群组或频道中最近收到 event 的 bot 及其最后一次收到的时间，与 `RoundRobin` 的轮询位置
+#[derive(Debug, Default)]
+struct Chat {
+    bots: Vec<(Selft, Instant)>,
+    round: usize,
+}
+
+/// 超过该时长未收到 event 的 bot 视为已离开群组，不再参与选举
+const CHAT_TTL: Duration = Duration::from_secs(600);
+
+/// 多个 bot 位于同一群组时，为同一 event 选出唯一响应的 bot
+#[derive(Debug)]
+pub(crate) struct Arbiter {
+    elections: DashMap<EventKey, Election>,
+    chats: DashMap<(String, String), Chat>,
+    pruned: Mutex<Instant>,
+}
+
+impl Default for Arbiter {
+    fn default() -> Self {
+        Self {
+            elections: DashMap::default(),
+            chats: DashMap::default(),
+            pruned: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+impl Arbiter {
+    fn elect(
+        &self,
+        config: &ArbitrationConfig,
+        key: &EventKey,
+        mut candidates: Vec<Selft>,
+    ) -> Selft {
+        match config.policy {
+            ElectionPolicy::Priority => {
+                let rank = |selft: &Selft| {
+                    config
+                        .priority
+                        .iter()
+                        .position(|id| id == &selft.user_id)
+                        .unwrap_or(usize::MAX)
+                };
+                // min_by_key 在并列时保留先到达的 bot
+                candidates.into_iter().min_by_key(rank).unwrap()
+            }
+            ElectionPolicy::LowestLatency => candidates.remove(0),
+            ElectionPolicy::RoundRobin => {
+                candidates.sort_by(|a, b| a.user_id.cmp(&b.user_id));
+                let mut chat = self.chats.entry(key.chat_key()).or_default();
+                let winner = candidates.swap_remove(chat.round % candidates.len());
+                chat.round = chat.round.wrapping_add(1);
+                winner
+            }
+        }
+    }
+
+    /// 当前 bot 是否应响应该 event
+    ///
+    /// 先到达的 event 等待 `window` 收集其他 bot 的同一 event 后进行选举，
+    /// `LowestLatency` 与排在首位的 `Priority` bot 无需等待；后到达的 bot 等待选举结果。
+    /// 近 `CHAT_TTL` 内该群组只有当前 bot 收到过 event 时同样无需等待，
+    /// 因此新 bot 加入群组后收到的第一个 event 仍可能由原有 bot 响应。
+    pub(crate) async fn won(&self, config: &ArbitrationConfig, event: &Event) -> bool {
+        let Some(selft) = event.selft() else {
+            return true;
+        };
+        let Some(key) = EventKey::new(event, &selft) else {
+            return true;
+        };
+        let window = Duration::from_millis(config.window);
+        let now = Instant::now();
+        self.prune(now, window * 2);
+        let alone = self.see(&key, &selft, now);
+
+        let waiting = match self.elections.entry(key.clone()) {
+            Entry::Occupied(mut entry) if now.duration_since(entry.get().start) < window * 2 => {
+                let election = entry.get_mut();
+                if !election.candidates.contains(&selft) {
+                    election.candidates.push(selft.clone());
+                }
+                Some(election.winner.subscribe())
+            }
+            entry => {
+                let immediate = alone
+                    || match config.policy {
+                        ElectionPolicy::LowestLatency => true,
+                        ElectionPolicy::Priority => config.priority.first() == Some(&selft.user_id),
+                        ElectionPolicy::RoundRobin => false,
+                    };
+                let (winner, _) = watch::channel(immediate.then(|| selft.clone()));
+                let election = Election {
+                    start: now,
+                    candidates: vec![selft.clone()],
+                    winner,
+                };
+                match entry {
+                    Entry::Occupied(mut entry) => {
+                        entry.insert(election);
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(election);
+                    }
+                }
+                if immediate {
+                    return true;
+                }
+                None
+            }
+        };
+        if let Some(mut winner) = waiting {
+            // 选举方未能给出结果时自行响应，避免无人响应
+            let elected = tokio::time::timeout(window * 2, async {
+                while winner.borrow_and_update().is_none() {
+                    winner.changed().await.ok()?;
+                }
+                winner.borrow().clone()
+            });
+            return match elected.await {
+                Ok(Some(winner)) => winner == selft,
+                _ => true,
+            };
+        }
+
+        tokio::time::sleep(window).await;
+        let Some(candidates) = self.elections.get(&key).map(|e| e.candidates.clone()) else {
+            return true;
+        };
+        let winner = self.elect(config, &key, candidates);
+        if let Some(election) = self.elections.get(&key) {
+            election.winner.send_replace(Some(winner.clone()));
+        }
+        winner == selft
+    }
+
+    /// 记录 `selft` 在该群组收到 event，返回近 `CHAT_TTL` 内是否只有 `selft` 收到过 event
+    fn see(&self, key: &EventKey, selft: &Selft, now: Instant) -> bool {
+        let mut chat = self.chats.entry(key.chat_key()).or_default();
+        chat.bots
+            .retain(|(bot, seen)| bot != selft && now.duration_since(*seen) < CHAT_TTL);
+        chat.bots.push((selft.clone(), now));
+        chat.bots.len() == 1
+    }
+
+    /// 每隔 `ttl` 清理一次已结束的选举与 `CHAT_TTL` 内没有 bot 收到 event 的群组，
+    /// 过期的选举在查找时也会被替换
+    fn prune(&self, now: Instant, ttl: Duration) {
+        let mut pruned = self.pruned.lock().unwrap_or_else(|e| e.into_inner());
+        if now.duration_since(*pruned) >= ttl {
+            *pruned = now;
+            self.elections
+                .retain(|_, e| now.duration_since(e.start) < ttl);
+            self.chats.retain(|_, chat| {
+                chat.bots
+                    .iter()
+                    .any(|(_, seen)| now.duration_since(*seen) < CHAT_TTL)
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Arbiter, EventKey, CHAT_TTL};
+    use crate::matcher::mock::bot_message;
+    use crate::{ArbitrationConfig, ElectionPolicy};
+    use std::time::{Duration, Instant};
+    use walle_core::structs::Selft;
+
+    fn selft(user_id: &str) -> Selft {
+        Selft {
+            platform: "qq".to_owned(),
+            user_id: user_id.to_owned(),
+        }
+    }
+
+    #[test]
+    fn elect() {
+        let arbiter = Arbiter::default();
+        let key = EventKey {
+            platform: "qq".to_owned(),
+            chat: "group".to_owned(),
+            user_id: "user".to_owned(),
+            content: "hello".to_owned(),
+        };
+        let candidates = vec![selft("b"), selft("a"), selft("c")];
+        let mut config = ArbitrationConfig {
+            priority: vec!["c".to_owned(), "a".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(arbiter.elect(&config, &key, candidates.clone()), selft("c"));
+        config.policy = ElectionPolicy::LowestLatency;
+        assert_eq!(arbiter.elect(&config, &key, candidates.clone()), selft("b"));
+        config.policy = ElectionPolicy::RoundRobin;
+        let winners: Vec<Selft> = (0..4)
+            .map(|_| arbiter.elect(&config, &key, candidates.clone()))
+            .collect();
+        assert_eq!(
+            winners,
+            vec![selft("a"), selft("b"), selft("c"), selft("a")]
+        );
+    }
+
+    #[tokio::test]
+    async fn concurrent_won() {
+        let arbiter = Arbiter::default();
+        let config = ArbitrationConfig {
+            priority: vec!["c".to_owned(), "b".to_owned()],
+            window: 20,
+            ..Default::default()
+        };
+        // 两个 bot 均已在群组中收到过 event，之后的 event 才需要选举
+        let (a, b) = (bot_message("a", "hi"), bot_message("b", "hi"));
+        assert!(arbiter.won(&config, &a).await);
+        assert!(!arbiter.won(&config, &b).await);
+
+        let (a, b) = (bot_message("a", "hello"), bot_message("b", "hello"));
+        let late = async {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            arbiter.won(&config, &b).await
+        };
+        let (a_won, b_won) = tokio::join!(arbiter.won(&config, &a), late);
+        // 后到达但优先级更高的 b 等待选举结果并当选
+        assert!(!a_won && b_won);
+        // 选举结束后到达的 bot 直接获得结果
+        let c = bot_message("c", "hello");
+        assert!(!arbiter.won(&config, &c).await);
+    }
+
+    #[tokio::test]
+    async fn alone() {
+        let arbiter = Arbiter::default();
+        let config = ArbitrationConfig {
+            policy: ElectionPolicy::RoundRobin,
+            window: 1000,
+            ..Default::default()
+        };
+        // 群组中只有一个 bot 时无需等待选举窗口
+        let event = bot_message("a", "hello");
+        let won = tokio::time::timeout(Duration::from_millis(100), arbiter.won(&config, &event));
+        assert_eq!(won.await, Ok(true));
+        assert_eq!(arbiter.chats.len(), 1);
+
+        let later = Instant::now() + CHAT_TTL;
+        arbiter.prune(later, Duration::from_millis(2000));
+        assert!(arbiter.elections.is_empty());
+        assert!(arbiter.chats.is_empty());
+    }
+}
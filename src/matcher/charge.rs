@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use crate::Session;
+
+/// 在 rule 阶段登记、handler 实际执行前才扣除的额度，如 `builtin::cooldown` 与 `builtin::quota`
+pub(crate) trait Charge: Send + Sync {
+    /// 检查并扣除一次 `key` 的额度，两者须为同一原子操作，不足时不扣除并返回 Err，内容为可选的回复消息
+    fn try_consume(&self, key: &str, session: &Session) -> Result<(), Option<String>>;
+    /// 退还一次 `try_consume` 扣除的额度，用于之后的额度不足时回滚
+    fn refund(&self, key: &str, session: &Session);
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Charges(Vec<(Arc<dyn Charge>, String)>);
+
+impl Session {
+    /// 登记一次额度扣除，在 `run_handler` 执行 handler 前统一检查并扣除
+    pub(crate) fn defer_charge(&self, charge: Arc<dyn Charge>, key: String) {
+        let mut charges = self.extensions.get::<Charges>().unwrap_or_default();
+        charges.0.push((charge, key));
+        self.extensions.insert(charges);
+    }
+
+    /// 依次扣除所有登记的额度并返回 Ok，任一额度不足时退还已扣除的额度并返回其回复消息
+    pub(crate) fn charge(&self) -> Result<(), Option<String>> {
+        let Some(Charges(charges)) = self.extensions.remove::<Charges>() else {
+            return Ok(());
+        };
+        for (i, (charge, key)) in charges.iter().enumerate() {
+            if let Err(reply) = charge.try_consume(key, self) {
+                for (charge, key) in &charges[..i] {
+                    charge.refund(key, self);
+                }
+                return Err(reply);
+            }
+        }
+        Ok(())
+    }
+
+    /// 扣除 handler 未经 `run_handler` 执行时遗留的额度，返回是否有遗留
+    ///
+    /// 此时 handler 已执行，额度不足也无法阻止，仅保证计数。
+    pub(crate) fn settle_charges(&self) -> bool {
+        let Some(Charges(charges)) = self.extensions.remove::<Charges>() else {
+            return false;
+        };
+        for (charge, key) in &charges {
+            charge.try_consume(key, self).ok();
+        }
+        true
+    }
+}
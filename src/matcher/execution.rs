@@ -0,0 +1,160 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use dashmap::{mapref::entry::Entry, DashMap};
+use tokio::sync::oneshot;
+use walle_core::{event::Event, util::ValueMapExt};
+
+/// handler 在同一会话中的执行方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExecutionPolicy {
+    /// 并行执行
+    #[default]
+    Parallel,
+    /// 同一群组或频道中按 event 到达顺序依次执行，私聊时按用户区分
+    SequentialChat,
+    /// 同一用户按 event 到达顺序依次执行
+    SequentialUser,
+    /// 同一用户同时只执行一个，执行期间的 event 回复 `MatchersConfig::busy_reply`
+    SingleFlightUser,
+}
+
+impl ExecutionPolicy {
+    fn key(&self, event: &Event) -> Option<String> {
+        let extra = &event.extra;
+        let user_id = || extra.get_downcast::<String>("user_id").ok();
+        match self {
+            Self::Parallel => None,
+            Self::SequentialChat => extra
+                .get_downcast::<String>("group_id")
+                .ok()
+                .or_else(|| {
+                    let guild_id = extra.get_downcast::<String>("guild_id").ok()?;
+                    let channel_id = extra.get_downcast::<String>("channel_id").ok()?;
+                    Some(format!("{}:{}", guild_id, channel_id))
+                })
+                .or_else(user_id),
+            Self::SequentialUser | Self::SingleFlightUser => user_id(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ExecutionState {
+    next: AtomicU64,
+    /// 每个会话最后一个排队任务的编号与完成通知
+    tails: DashMap<String, (u64, oneshot::Receiver<()>)>,
+    running: DashMap<String, ()>,
+}
+
+/// 单个 matcher 的执行策略与排队状态，同一 matcher 的所有 event 共享
+#[derive(Debug, Clone, Default)]
+pub struct Execution {
+    pub policy: ExecutionPolicy,
+    state: Arc<ExecutionState>,
+}
+
+impl Execution {
+    pub fn new(policy: ExecutionPolicy) -> Self {
+        Self {
+            policy,
+            state: Arc::default(),
+        }
+    }
+
+    /// 按 event 到达顺序排队，`SingleFlightUser` 已有任务执行时返回 None
+    pub(crate) fn enter(&self, event: &Event) -> Option<Ticket> {
+        let Some(key) = self.policy.key(event) else {
+            return Some(Ticket::default());
+        };
+        let mut ticket = Ticket::default();
+        ticket.state = Some(self.state.clone());
+        if self.policy == ExecutionPolicy::SingleFlightUser {
+            match self.state.running.entry(key.clone()) {
+                Entry::Occupied(_) => return None,
+                Entry::Vacant(entry) => entry.insert(()),
+            };
+            ticket.running = Some(key);
+        } else {
+            let id = self.state.next.fetch_add(1, Ordering::Relaxed);
+            let (tx, rx) = oneshot::channel();
+            ticket.prev = self.state.tails.insert(key.clone(), (id, rx)).map(|t| t.1);
+            ticket.done = Some(tx);
+            ticket.tail = Some((key, id));
+        }
+        Some(ticket)
+    }
+}
+
+/// 排队凭证，drop 时允许下一个任务执行
+#[derive(Default)]
+pub(crate) struct Ticket {
+    state: Option<Arc<ExecutionState>>,
+    prev: Option<oneshot::Receiver<()>>,
+    done: Option<oneshot::Sender<()>>,
+    tail: Option<(String, u64)>,
+    running: Option<String>,
+}
+
+impl Ticket {
+    /// 等待之前的任务执行完毕
+    pub(crate) async fn ready(&mut self) {
+        if let Some(prev) = &mut self.prev {
+            let _ = prev.await;
+            self.prev = None;
+        }
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        let Some(state) = &self.state else {
+            return;
+        };
+        if let Some((key, id)) = &self.tail {
+            state.tails.remove_if(key, |_, (tail, _)| tail == id);
+        }
+        if let Some(key) = &self.running {
+            state.running.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Execution, ExecutionPolicy};
+    use std::time::Duration;
+    use walle_core::{event::Event, value_map};
+
+    fn event(user_id: &str) -> Event {
+        Event {
+            id: String::default(),
+            time: 0.0,
+            ty: "message".to_owned(),
+            detail_type: "private".to_owned(),
+            sub_type: String::default(),
+            extra: value_map! { "user_id": user_id },
+        }
+    }
+
+    #[tokio::test]
+    async fn execution() {
+        let sequential = Execution::new(ExecutionPolicy::SequentialUser);
+        let first = sequential.enter(&event("a")).unwrap();
+        let mut second = sequential.enter(&event("a")).unwrap();
+        let mut other = sequential.enter(&event("b")).unwrap();
+        other.ready().await;
+        let waiting = tokio::time::timeout(Duration::from_millis(10), second.ready());
+        assert!(waiting.await.is_err());
+        drop(first);
+        second.ready().await;
+
+        let single = Execution::new(ExecutionPolicy::SingleFlightUser);
+        let running = single.enter(&event("a")).unwrap();
+        assert!(single.enter(&event("a")).is_none());
+        drop(running);
+        assert!(single.enter(&event("a")).is_some());
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    ops::Deref,
+    sync::{Arc, Mutex},
+};
+
+use walle_core::{prelude::async_trait, WalleError, WalleResult};
+
+use crate::{FromSessionPart, Session};
+
+type AnyMap = HashMap<TypeId, Arc<dyn Any + Send + Sync>>;
+
+/// 以类型为键的 session 扩展数据
+///
+/// pre-handler 与 rule 写入，handler 通过 `Ext<T>` extractor 读取。
+/// 同一 session 的所有克隆共享同一份数据，全局 pre-handler 写入的数据会复制给每个 matcher。
+#[derive(Default, Clone)]
+pub struct Extensions(Arc<Mutex<AnyMap>>);
+
+impl Extensions {
+    fn lock(&self) -> std::sync::MutexGuard<'_, AnyMap> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 复制当前数据，之后的写入互不影响
+    pub(crate) fn fork(&self) -> Self {
+        Self(Arc::new(Mutex::new(self.lock().clone())))
+    }
+
+    /// 写入数据，返回同一类型的旧值
+    pub fn insert<T: Clone + Send + Sync + 'static>(&self, value: T) -> Option<T> {
+        self.lock()
+            .insert(TypeId::of::<T>(), Arc::new(value))
+            .and_then(|old| old.downcast_ref::<T>().cloned())
+    }
+
+    pub fn get<T: Clone + Send + Sync + 'static>(&self) -> Option<T> {
+        self.lock()
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>())
+            .cloned()
+    }
+
+    pub fn remove<T: Clone + Send + Sync + 'static>(&self) -> Option<T> {
+        self.lock()
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref::<T>().cloned())
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.lock().contains_key(&TypeId::of::<T>())
+    }
+}
+
+impl std::fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.lock().len())
+            .finish()
+    }
+}
+
+/// session 扩展数据 extractor，数据不存在时提取失败
+#[derive(Debug, Clone)]
+pub struct Ext<T>(pub T);
+
+impl<T> Deref for Ext<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl<T: Clone + Send + Sync + 'static> FromSessionPart for Ext<T> {
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        session.extensions.get().map(Self).ok_or_else(|| {
+            WalleError::Other(format!(
+                "extension {} not found",
+                std::any::type_name::<T>()
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Extensions;
+
+    #[test]
+    fn extensions() {
+        let ext = Extensions::default();
+        assert_eq!(ext.insert(1u32), None);
+        assert_eq!(ext.insert(2u32), Some(1));
+        let shared = ext.clone();
+        shared.insert("keyword");
+        assert_eq!(ext.get::<&str>(), Some("keyword"));
+        let forked = ext.fork();
+        assert_eq!(ext.remove::<u32>(), Some(2));
+        assert!(!shared.contains::<u32>());
+        assert_eq!(forked.get::<u32>(), Some(2));
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use dashmap::{mapref::entry::Entry, DashMap};
+use walle_core::{event::Event, structs::Selft, util::ValueMapExt};
+
+use crate::FilterConfig;
+
+/// event 被过滤的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Dropped {
+    Duplicate,
+    Stale,
+    FromSelf,
+}
+
+/// `Matchers::call` 中最先执行的 event 过滤，并统计被丢弃的 event 数量
+///
+/// 去重以 bot 与 event id 为键，不同 bot 收到的同一 id 不视为重复。
+#[derive(Debug)]
+pub(crate) struct EventFilter {
+    seen: DashMap<(Option<Selft>, String), Instant>,
+    pruned: Mutex<Instant>,
+    duplicate: AtomicU64,
+    stale: AtomicU64,
+    from_self: AtomicU64,
+}
+
+impl Default for EventFilter {
+    fn default() -> Self {
+        Self {
+            seen: DashMap::default(),
+            pruned: Mutex::new(Instant::now()),
+            duplicate: AtomicU64::default(),
+            stale: AtomicU64::default(),
+            from_self: AtomicU64::default(),
+        }
+    }
+}
+
+impl EventFilter {
+    fn counter(&self, dropped: Dropped) -> &AtomicU64 {
+        match dropped {
+            Dropped::Duplicate => &self.duplicate,
+            Dropped::Stale => &self.stale,
+            Dropped::FromSelf => &self.from_self,
+        }
+    }
+
+    fn check(&self, config: &FilterConfig, event: &Event) -> Option<Dropped> {
+        if config.ignore_self {
+            let user_id = event.extra.try_get_as_ref::<&str>("user_id").ok();
+            if user_id.is_some_and(|id| event.selft().is_some_and(|s| s.user_id == id)) {
+                return Some(Dropped::FromSelf);
+            }
+        }
+        if config.max_age > 0 {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64();
+            if now - event.time > config.max_age as f64 {
+                return Some(Dropped::Stale);
+            }
+        }
+        if config.dedup_window > 0 && !event.id.is_empty() {
+            let now = Instant::now();
+            let window = Duration::from_secs(config.dedup_window);
+            self.prune(now, window);
+            match self.seen.entry((event.selft(), event.id.clone())) {
+                Entry::Occupied(seen) if now.duration_since(*seen.get()) < window => {
+                    return Some(Dropped::Duplicate)
+                }
+                Entry::Occupied(mut seen) => {
+                    seen.insert(now);
+                }
+                Entry::Vacant(seen) => {
+                    seen.insert(now);
+                }
+            }
+        }
+        None
+    }
+
+    /// 每隔 `window` 清理一次过期的 event id，过期项在查找时也会被覆盖
+    fn prune(&self, now: Instant, window: Duration) {
+        let mut pruned = self.pruned.lock().unwrap_or_else(|e| e.into_inner());
+        if now.duration_since(*pruned) >= window {
+            *pruned = now;
+            self.seen
+                .retain(|_, seen| now.duration_since(*seen) < window);
+        }
+    }
+
+    /// event 是否应被丢弃，丢弃时记录原因与累计数量
+    pub(crate) fn drop_event(&self, config: &FilterConfig, event: &Event) -> bool {
+        let Some(dropped) = self.check(config, event) else {
+            return false;
+        };
+        self.counter(dropped).fetch_add(1, Ordering::Relaxed);
+        tracing::debug!(
+            target: "Walle",
+            "event {} dropped: {:?} (dropped duplicate: {}, stale: {}, from self: {})",
+            event.id,
+            dropped,
+            self.duplicate.load(Ordering::Relaxed),
+            self.stale.load(Ordering::Relaxed),
+            self.from_self.load(Ordering::Relaxed),
+        );
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Dropped, EventFilter};
+    use crate::FilterConfig;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use walle_core::{event::Event, value_map};
+
+    fn event(id: &str, age: f64, user_id: &str) -> Event {
+        bot_event("bot", id, age, user_id)
+    }
+
+    fn bot_event(bot: &str, id: &str, age: f64, user_id: &str) -> Event {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs_f64();
+        let extra = value_map! {
+            "user_id": user_id,
+            "self": value_map! { "platform": "qq", "user_id": bot }
+        };
+        Event {
+            id: id.to_owned(),
+            time: now - age,
+            ty: "message".to_owned(),
+            detail_type: "private".to_owned(),
+            sub_type: String::default(),
+            extra,
+        }
+    }
+
+    #[test]
+    fn filter_events() {
+        let filter = EventFilter::default();
+        let config = FilterConfig {
+            dedup_window: 60,
+            max_age: 60,
+            ignore_self: true,
+        };
+        assert_eq!(filter.check(&config, &event("1", 0.0, "user")), None);
+        assert_eq!(
+            filter.check(&config, &bot_event("other", "1", 0.0, "user")),
+            None
+        );
+        assert_eq!(
+            filter.check(&config, &event("1", 0.0, "user")),
+            Some(Dropped::Duplicate)
+        );
+        assert_eq!(
+            filter.check(&config, &event("2", 120.0, "user")),
+            Some(Dropped::Stale)
+        );
+        assert_eq!(
+            filter.check(&config, &event("3", 0.0, "bot")),
+            Some(Dropped::FromSelf)
+        );
+        let config = FilterConfig::default();
+        assert_eq!(filter.check(&config, &event("1", 0.0, "user")), None);
+        assert_eq!(filter.check(&config, &event("3", 0.0, "bot")), None);
+    }
+}
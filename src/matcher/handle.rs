@@ -1,10 +1,16 @@
-use crate::{FromSession, FromSessionPart};
+use crate::{Execution, ExecutionPolicy, FromSession, FromSessionPart};
 
 use super::Session;
-use std::{future::Future, sync::Arc};
+use std::{
+    future::Future,
+    sync::{Arc, OnceLock},
+};
 
 use async_trait::async_trait;
-use walle_core::WalleResult;
+use walle_core::{
+    segment::{IntoMessage, MsgSegment, Segments},
+    WalleError, WalleResult,
+};
 
 #[derive(Default, Debug, PartialEq, Eq)]
 pub enum Signal {
@@ -44,9 +50,137 @@ impl From<WalleResult<Signal>> for Signal {
     }
 }
 
+/// handler 返回值
+///
+/// `()` 不做处理，`Signal` 原样返回，`String`、`&'static str`、`MsgSegment` 与 `Segments` 将自动回复，
+/// 其他 `IntoMessage` 类型请使用 `Reply` 包装，
+/// `WalleResult<T>` 的错误将被记录，`MatchersConfig::reply_errors` 开启时同时回复错误信息。
+/// 仅在 `awaited` 模式下返回的 Signal 会影响事件传播。
+#[async_trait]
+pub trait HandlerOutput: Send + 'static {
+    async fn output(self, session: &Session) -> Signal;
+}
+
+#[async_trait]
+impl HandlerOutput for () {
+    async fn output(self, _session: &Session) -> Signal {
+        Signal::Matched
+    }
+}
+
+#[async_trait]
+impl HandlerOutput for Signal {
+    async fn output(self, _session: &Session) -> Signal {
+        self
+    }
+}
+
+#[async_trait]
+impl<T: HandlerOutput> HandlerOutput for WalleResult<T> {
+    async fn output(self, session: &Session) -> Signal {
+        match self {
+            Ok(t) => t.output(session).await,
+            Err(e) => {
+                tracing::warn!(target: "Walle", "matcher failed: {}", e);
+                if session.config.reply_errors {
+                    if let Err(e) = session.reply(e.to_string()).await {
+                        tracing::warn!(target: "Walle", "reply error failed: {}", e);
+                    }
+                }
+                Signal::Matched
+            }
+        }
+    }
+}
+
+/// 自动回复任意 `IntoMessage`
+pub struct Reply<M>(pub M);
+
+#[async_trait]
+impl<M: IntoMessage + Send + 'static> HandlerOutput for Reply<M> {
+    async fn output(self, session: &Session) -> Signal {
+        if let Err(e) = session.reply(self.0).await {
+            tracing::warn!(target: "Walle", "auto reply failed: {}", e);
+        }
+        Signal::Matched
+    }
+}
+
+macro_rules! impl_reply_output {
+    ($($t: ty),*) => {
+        $(#[async_trait]
+        impl HandlerOutput for $t {
+            async fn output(self, session: &Session) -> Signal {
+                Reply(self).output(session).await
+            }
+        })*
+    };
+}
+
+impl_reply_output!(String, &'static str, MsgSegment, Segments);
+
+/// 扣除 rule 登记的额度后按 `options.execution` 排队执行 handler，`awaited` 为 false 时在新任务中执行并立即返回 `Signal::Matched`
+#[doc(hidden)]
+pub async fn run_handler<Fut>(fut: Fut, session: Session, options: &HandlerOptions) -> Signal
+where
+    Fut: Future + Send + 'static,
+    Fut::Output: HandlerOutput,
+{
+    let Some(mut ticket) = options.execution.enter(&session.event) else {
+        let busy = &session.config.busy_reply;
+        if !busy.is_empty() {
+            if let Err(e) = session.reply(busy.clone()).await {
+                tracing::warn!(target: "Walle", "reply please wait failed: {}", e);
+            }
+        }
+        return Signal::Matched;
+    };
+    if let Err(reply) = session.charge() {
+        if let Some(reply) = reply {
+            if let Err(e) = session.reply(reply).await {
+                tracing::warn!(target: "Walle", "reply limit rejection failed: {}", e);
+            }
+        }
+        return Signal::NotMatch;
+    }
+    let run = async move {
+        ticket.ready().await;
+        fut.await.output(&session).await
+    };
+    if options.awaited {
+        run.await
+    } else {
+        tokio::spawn(run);
+        Signal::Matched
+    }
+}
+
+/// 本次 event 中命令参数有误的用法说明，由 `Matchers` 在没有 matcher 处理 event 时回复
+#[derive(Clone, Default)]
+pub(crate) struct UsageFailure(pub(crate) Arc<OnceLock<String>>);
+
+/// extractor 失败时视为不匹配，命令已被认领则记录其用法说明
+///
+/// 同一命令可能由多个 matcher 处理（如群聊与私聊各一个），
+/// 因此不立即回复，而是在所有 matcher 均未处理时由 `Matchers` 按 `MatchersConfig::usage_reply` 回复。
+#[doc(hidden)]
+pub fn extract_failed(session: &Session, e: WalleError, options: &HandlerOptions) -> Signal {
+    tracing::debug!(target: "Walle", "extract failed: {}", e);
+    if let Some(usage) = session.claimed_command().filter(|_| !options.silent) {
+        if let Some(UsageFailure(failure)) = session.extensions.get() {
+            failure.get_or_init(|| usage.to_owned());
+        }
+    }
+    Signal::NotMatch
+}
+
 #[async_trait]
 pub trait MatcherHandler {
     async fn handle(&self, session: Session) -> Signal;
+    /// handler 对应的命令名与别名，注册时收集用于命令提示
+    fn commands(&self) -> Vec<String> {
+        vec![]
+    }
     fn boxed(self) -> Box<Self>
     where
         Self: Sized,
@@ -76,19 +210,61 @@ impl<T: ArcMatcherHandler> MatcherHandler for Arc<T> {
     }
 }
 
+/// handler 执行选项
+#[derive(Debug, Clone, Default)]
+pub struct HandlerOptions {
+    /// 等待 handler 执行完毕，以其返回的 Signal 决定是否继续传播事件
+    pub awaited: bool,
+    /// 命令输入有误时不回复错误信息
+    pub silent: bool,
+    /// 同一会话中的执行方式
+    pub execution: Execution,
+}
+
 #[async_trait]
 pub trait _MatcherHandler<T> {
-    async fn _handle(&self, session: Session) -> Signal;
+    async fn _handle(&self, session: Session, options: HandlerOptions) -> Signal;
+    fn _commands(&self) -> Vec<String> {
+        vec![]
+    }
 }
 
 pub fn matcher<H, T>(h: H) -> BoxedMatcherHandler<H, T>
 where
     H: _MatcherHandler<T>,
 {
-    BoxedMatcherHandler(h, std::marker::PhantomData::default())
+    BoxedMatcherHandler {
+        handler: h,
+        options: HandlerOptions::default(),
+        _t: std::marker::PhantomData,
+    }
 }
 
-pub struct BoxedMatcherHandler<H, T>(H, std::marker::PhantomData<T>);
+pub struct BoxedMatcherHandler<H, T> {
+    handler: H,
+    options: HandlerOptions,
+    _t: std::marker::PhantomData<T>,
+}
+
+impl<H, T> BoxedMatcherHandler<H, T> {
+    /// 等待 handler 执行完毕，以其返回的 Signal 决定是否继续传播事件
+    pub fn awaited(mut self) -> Self {
+        self.options.awaited = true;
+        self
+    }
+
+    /// 命令输入有误时不回复错误信息，仅视为不匹配
+    pub fn silent(mut self) -> Self {
+        self.options.silent = true;
+        self
+    }
+
+    /// 设置同一会话中的执行方式，默认并行
+    pub fn execution(mut self, policy: ExecutionPolicy) -> Self {
+        self.options.execution = Execution::new(policy);
+        self
+    }
+}
 
 impl<H, T> MatcherHandler for BoxedMatcherHandler<H, T>
 where
@@ -104,56 +280,60 @@ where
         'life0: 'async_trait,
         Self: 'async_trait,
     {
-        self.0._handle(session)
+        self.handler._handle(session, self.options.clone())
+    }
+    fn commands(&self) -> Vec<String> {
+        self.handler._commands()
     }
 }
 
 impl<F, Fut> _MatcherHandler<()> for F
 where
     F: Fn() -> Fut + Send + Sync + 'static,
-    Fut: Future<Output = ()> + Send + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: HandlerOutput,
 {
     fn _handle<'a, 't>(
         &'a self,
-        _session: Session,
+        session: Session,
+        options: HandlerOptions,
     ) -> core::pin::Pin<Box<dyn core::future::Future<Output = Signal> + core::marker::Send + 't>>
     where
         'a: 't,
         Self: 't,
     {
-        Box::pin(async move {
-            tokio::spawn(self());
-            Signal::Matched
-        })
+        Box::pin(async move { run_handler(self(), session, &options).await })
     }
 }
 
 impl<F, T, Fut> _MatcherHandler<T> for F
 where
     F: Fn(T) -> Fut + Send + Sync + 'static,
-    Fut: Future<Output = ()> + Send + 'static,
+    Fut: Future + Send + 'static,
+    Fut::Output: HandlerOutput,
     T: FromSession + Send,
 {
     fn _handle<'a, 't>(
         &'a self,
         session: Session,
+        options: HandlerOptions,
     ) -> core::pin::Pin<Box<dyn core::future::Future<Output = Signal> + core::marker::Send + 't>>
     where
         'a: 't,
         Self: 't,
     {
         Box::pin(async move {
+            let output_session = session.clone();
             let t = match T::from_session(session).await {
                 Ok(t) => t,
-                Err(e) => {
-                    tracing::debug!(target: "Walle", "from session failed: {}", e);
-                    return Signal::NotMatch;
-                }
+                Err(e) => return extract_failed(&output_session, e, &options),
             };
-            tokio::spawn(self(t));
-            Signal::Matched
+            run_handler(self(t), output_session, &options).await
         })
     }
+    fn _commands(&self) -> Vec<String> {
+        T::commands()
+    }
 }
 
 macro_rules! impl_matcher_handler {
@@ -162,13 +342,15 @@ macro_rules! impl_matcher_handler {
         impl<F, $($ty,)* T, Fut> _MatcherHandler<($($ty,)* T)> for F
         where
             F: Fn($($ty,)* T) -> Fut + Send + Sync + 'static,
-            Fut: Future<Output = ()> + Send + 'static,
+            Fut: Future + Send + 'static,
+            Fut::Output: HandlerOutput,
             $($ty: FromSessionPart + Send,)*
             T: FromSession + Send,
         {
             fn _handle<'a, 't>(
                 &'a self,
                 mut session: Session,
+                options: HandlerOptions,
             ) -> core::pin::Pin<Box<dyn core::future::Future<Output = Signal> + core::marker::Send + 't>>
             where
                 'a: 't,
@@ -177,22 +359,21 @@ macro_rules! impl_matcher_handler {
                 Box::pin(async move {
                     $(let $ty = match $ty::from_session_part(&mut session).await {
                         Ok(t) => t,
-                        Err(e) => {
-                            tracing::debug!(target: "Walle", "from session part failed: {}", e);
-                            return Signal::NotMatch;
-                        }
+                        Err(e) => return extract_failed(&session, e, &options),
                     };)*
+                    let output_session = session.clone();
                     let t = match T::from_session(session).await {
                         Ok(t) => t,
-                        Err(e) => {
-                            tracing::debug!(target: "Walle", "from session failed: {}", e);
-                            return Signal::NotMatch;
-                        }
+                        Err(e) => return extract_failed(&output_session, e, &options),
                     };
-                    tokio::spawn(self($($ty,)* t));
-                    Signal::Matched
+                    run_handler(self($($ty,)* t), output_session, &options).await
                 })
             }
+            fn _commands(&self) -> Vec<String> {
+                let mut commands = T::commands();
+                $(commands.extend($ty::commands());)*
+                commands
+            }
         }
     };
 }
@@ -207,54 +388,14 @@ impl_matcher_handler!(T0, T1, T2, T3, T4, T5, T6);
 impl_matcher_handler!(T0, T1, T2, T3, T4, T5, T6, T7);
 impl_matcher_handler!(T0, T1, T2, T3, T4, T5, T6, T7, T8);
 
-#[cfg(test)]
-mod test {
-    pub struct StructMatcher;
-
-    impl StructMatcher {
-        async fn method(
-            &self,
-            _event: crate::walle_core::event::GroupMessageEvent,
-            _session: crate::Session,
-        ) {
-            ()
-        }
-        #[allow(dead_code)]
-        async fn failable_method(
-            self: &std::sync::Arc<Self>,
-            _event: crate::walle_core::event::GroupMessageEvent,
-            _session: crate::Session,
-        ) -> crate::walle_core::WalleResult<()> {
-            Ok(())
-        }
-    }
-
-    #[crate::walle_core::prelude::async_trait]
-    impl crate::ArcMatcherHandler for StructMatcher {
-        async fn handle(self: &std::sync::Arc<Self>, mut session: crate::Session) -> crate::Signal {
-            use crate::{FromSession, FromSessionPart};
-            let event =
-                match walle_core::event::GroupMessageEvent::from_session_part(&mut session).await {
-                    Ok(e) => e,
-                    Err(e) => {
-                        tracing::debug!(target: "Walle", "from session part failed: {}", e);
-                        return crate::Signal::NotMatch;
-                    }
-                };
-            let session = match crate::Session::from_session(session).await {
-                Ok(e) => e,
-                Err(e) => {
-                    tracing::debug!(target: "Walle", "from session failed: {}", e);
-                    return crate::Signal::NotMatch;
-                }
-            };
-            let new = self.clone();
-            crate::tokio::spawn(async move { new.method(event, session).await });
-            crate::Signal::Matched
-        }
-    }
-}
+/// `matcher!` 展开时调用，使旧宏的使用处产生弃用警告（`#[deprecated]` 无法直接作用于 `macro_rules!`）
+///
+/// 仅为兼容保留，将与 `matcher!` 一同在 0.2.0 移除。
+#[doc(hidden)]
+#[deprecated(note = "use #[walle::plugin] with #[handler] methods instead of matcher!")]
+pub fn __deprecated_matcher_macro() {}
 
+/// 已弃用，请改用 `#[plugin]` 与 `#[handler]`，将在 0.2.0 移除
 #[macro_export]
 macro_rules! matcher {
     ($s: ident, $m: ident, $($i: ident: $t: ty),*) => {
@@ -262,6 +403,7 @@ macro_rules! matcher {
         impl walle::ArcMatcherHandler for $s {
             async fn handle(self: &std::sync::Arc<Self>, mut session: walle::Session) -> walle::Signal {
                 use walle::{FromSession, FromSessionPart};
+                walle::__deprecated_matcher_macro();
                 $(let $i =
                     match <$t>::from_session_part(&mut session).await {
                         Ok(e) => e,
@@ -288,6 +430,7 @@ macro_rules! matcher {
         impl walle::ArcMatcherHandler for $s {
             async fn handle(self: &std::sync::Arc<Self>, mut session: walle::Session) -> walle::Signal {
                 use walle::{FromSession, FromSessionPart};
+                walle::__deprecated_matcher_macro();
                 $(let $i =
                     match <$t>::from_session_part(&mut session).await {
                         Ok(e) => e,
@@ -312,3 +455,235 @@ macro_rules! matcher {
         }
     };
 }
+
+/// 由 `#[walle::plugin]` 生成，将插件中的所有 handler 注册为 matcher
+pub trait Plugin: Send + Sync + 'static {
+    fn matchers(self: Arc<Self>) -> Vec<crate::Matcher>;
+}
+
+#[cfg(test)]
+#[allow(dead_code)]
+mod test {
+    pub struct StructMatcher;
+
+    impl StructMatcher {
+        async fn method(
+            &self,
+            _event: crate::walle_core::event::GroupMessageEvent,
+            _session: crate::Session,
+        ) {
+        }
+        #[allow(dead_code)]
+        async fn failable_method(
+            self: &std::sync::Arc<Self>,
+            _event: crate::walle_core::event::GroupMessageEvent,
+            _session: crate::Session,
+        ) -> crate::walle_core::WalleResult<()> {
+            Ok(())
+        }
+    }
+
+    #[crate::walle_core::prelude::async_trait]
+    impl crate::ArcMatcherHandler for StructMatcher {
+        async fn handle(self: &std::sync::Arc<Self>, mut session: crate::Session) -> crate::Signal {
+            use crate::{FromSession, FromSessionPart};
+            let event =
+                match walle_core::event::GroupMessageEvent::from_session_part(&mut session).await {
+                    Ok(e) => e,
+                    Err(e) => {
+                        tracing::debug!(target: "Walle", "from session part failed: {}", e);
+                        return crate::Signal::NotMatch;
+                    }
+                };
+            let session = match crate::Session::from_session(session).await {
+                Ok(e) => e,
+                Err(e) => {
+                    tracing::debug!(target: "Walle", "from session failed: {}", e);
+                    return crate::Signal::NotMatch;
+                }
+            };
+            let new = self.clone();
+            crate::tokio::spawn(async move { new.method(event, session).await });
+            crate::Signal::Matched
+        }
+    }
+
+    #[test]
+    fn handler_outputs() {
+        use crate::{matcher, MatcherHandler, Matchers, Reply, Session, Signal};
+        use walle_core::{segment::MsgSegment, WalleResult};
+
+        let _ = Matchers::default()
+            .with_global_pre_handler(crate::builtin::trim(true))
+            .with_global_rule(crate::builtin::start_with("ping"))
+            .add_matcher(matcher(|_: Session| async { "pong" }).boxed())
+            .add_matcher(matcher(|_: Session| async { Reply(MsgSegment::from("pong")) }).boxed())
+            .add_matcher(
+                matcher(|_: Session| async { WalleResult::Ok(String::from("pong")) }).boxed(),
+            )
+            .add_matcher(
+                matcher(|_: Session| async { Signal::MatchAndBlock })
+                    .awaited()
+                    .boxed(),
+            )
+            .add_matcher(
+                matcher(|_: Session| async { "pong" })
+                    .execution(crate::ExecutionPolicy::SingleFlightUser)
+                    .boxed(),
+            );
+    }
+
+    #[tokio::test]
+    async fn dispatch_outputs() {
+        use crate::builtin::start_with;
+        use crate::matcher::mock::{dispatch, group_message, MockCaller};
+        use crate::{matcher, MatcherHandler, Matchers, MatchersConfig, Rule, Session, Signal};
+        use walle_core::{WalleError, WalleResult};
+
+        let matchers = Matchers::default()
+            .add_matcher(
+                start_with("ping")
+                    .layer(matcher(|_: Session| async { "pong" }))
+                    .boxed(),
+            )
+            .add_matcher(
+                start_with("fail")
+                    .layer(matcher(|_: Session| async {
+                        WalleResult::<()>::Err(WalleError::Other("boom".to_owned()))
+                    }))
+                    .boxed(),
+            )
+            .add_matcher(
+                start_with("block")
+                    .layer(
+                        matcher(|s: Session| async move {
+                            s.reply("blocked").await.ok();
+                            Signal::MatchAndBlock
+                        })
+                        .awaited(),
+                    )
+                    .boxed(),
+            )
+            .add_matcher(
+                start_with("block")
+                    .layer(matcher(|_: Session| async { "leaked" }))
+                    .boxed(),
+            );
+        let caller = std::sync::Arc::new(MockCaller::default());
+        let config = MatchersConfig {
+            reply_errors: true,
+            ..Default::default()
+        };
+        let events = ["ping", "fail", "block"]
+            .into_iter()
+            .map(|text| group_message("user", text))
+            .collect();
+        dispatch(&matchers, caller.clone(), config, events).await;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let mut sent = caller.sent();
+        sent.sort();
+        assert_eq!(sent, vec!["blocked", "boom", "pong"]);
+    }
+
+    #[tokio::test]
+    async fn busy_reply() {
+        use crate::matcher::mock::{dispatch, group_message, MockCaller};
+        use crate::{matcher, ExecutionPolicy, MatcherHandler, Matchers, MatchersConfig, Session};
+
+        let matchers = Matchers::default().add_matcher(
+            matcher(|_: Session| async {
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                "done"
+            })
+            .execution(ExecutionPolicy::SingleFlightUser)
+            .boxed(),
+        );
+        for (busy, expected) in [
+            (None, vec!["请等待上一个请求处理完毕", "done"]),
+            (Some(""), vec!["done"]),
+        ] {
+            let caller = std::sync::Arc::new(MockCaller::default());
+            let mut config = MatchersConfig::default();
+            if let Some(busy) = busy {
+                config.busy_reply = busy.to_owned();
+            }
+            let events = vec![group_message("user", "a"), group_message("user", "b")];
+            dispatch(&matchers, caller.clone(), config, events).await;
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            assert_eq!(caller.sent(), expected);
+        }
+    }
+
+    #[derive(crate::builtin::Command)]
+    #[command(crate = "crate")]
+    struct Roll {
+        #[allow(dead_code)]
+        sides: u32,
+    }
+
+    #[tokio::test]
+    async fn usage_failure() {
+        use crate::builtin::start_with;
+        use crate::matcher::mock::{dispatch, group_message, MockCaller};
+        use crate::{matcher, MatcherHandler, Matchers, Rule, Session};
+
+        let roll = || matcher(|_: Roll, _: Session| async { "rolled" }).boxed();
+        let caller = std::sync::Arc::new(MockCaller::default());
+        let matchers = Matchers::default().add_matcher(roll()).add_matcher(
+            start_with("roll")
+                .layer(matcher(|_: Session| async { "other" }))
+                .boxed(),
+        );
+        let events = vec![group_message("user", "roll x")];
+        dispatch(&matchers, caller.clone(), Default::default(), events).await;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(caller.sent(), vec!["other"]);
+
+        let caller = std::sync::Arc::new(MockCaller::default());
+        let matchers = Matchers::default().add_matcher(roll());
+        let events = vec![group_message("user", "roll x")];
+        dispatch(&matchers, caller.clone(), Default::default(), events).await;
+        assert_eq!(caller.sent(), vec!["参数有误\nUsage: roll <sides>"]);
+    }
+
+    pub struct PluginMatcher;
+
+    #[crate::plugin(crate = "crate")]
+    impl PluginMatcher {
+        #[handler(rule = crate::builtin::start_with("ping"), awaited)]
+        async fn ping(&self, _session: crate::Session) -> &'static str {
+            "pong"
+        }
+        #[handler(
+            pre_handler = crate::builtin::strip_prefix("echo "),
+            silent,
+            execution = crate::ExecutionPolicy::SequentialChat
+        )]
+        async fn echo(
+            self: &std::sync::Arc<Self>,
+            text: crate::builtin::PlainText,
+            _session: crate::Session,
+        ) -> crate::walle_core::WalleResult<String> {
+            Ok(text.0)
+        }
+        fn helper(&self) {}
+    }
+
+    #[tokio::test]
+    async fn plugin() {
+        use crate::matcher::mock::{dispatch, group_message, MockCaller};
+
+        let matchers = crate::Matchers::default().add_plugin(PluginMatcher);
+        assert_eq!(matchers.inner.len(), 2);
+        let caller = std::sync::Arc::new(MockCaller::default());
+        let events = ["ping", "echo hello", "pong"]
+            .into_iter()
+            .map(|text| group_message("user", text))
+            .collect();
+        dispatch(&matchers, caller.clone(), Default::default(), events).await;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        let mut sent = caller.sent();
+        sent.sort();
+        assert_eq!(sent, vec!["hello", "pong"]);
+    }
+}
@@ -1,14 +1,18 @@
-use super::MatcherHandler;
-use crate::{ActionCaller, Session, Signal};
-use crate::{MatchersConfig, MatchersHook};
+use super::arbiter::Arbiter;
+use super::filter::EventFilter;
+use super::suggest::Suggester;
+use super::{nickname::BotNames, ImplCache, MatcherHandler};
+use crate::{ActionCaller, PreHandler, Rule, Session, Signal};
+use crate::{ConfigStore, MatchersConfig, MatchersHook, Plugin, PluginSection, States};
 use async_trait::async_trait;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::{
     sync::{Mutex, RwLock},
     task::JoinHandle,
 };
-use tracing::info;
+use tracing::{debug, error, info, warn};
 use walle_core::prelude::WalleError;
 use walle_core::{
     action::Action, error::WalleResult, event::Event, resp::Resp, ActionHandler, EventHandler,
@@ -18,36 +22,281 @@ use walle_core::{
 pub type Matcher = Box<dyn MatcherHandler + Send + Sync + 'static>;
 pub type TempMatchers = Arc<Mutex<HashMap<String, Matcher>>>;
 
+/// 每个 event 在所有 matcher 之前执行一次的 pre-handler 或 rule
+enum GlobalLayer {
+    PreHandler(Box<dyn PreHandler + Send + Sync + 'static>),
+    Rule(Box<dyn Rule + Send + Sync + 'static>),
+}
+
+impl GlobalLayer {
+    async fn apply(&self, session: &mut Session) -> Signal {
+        match self {
+            Self::PreHandler(pre) => pre.pre_handle(session).await,
+            Self::Rule(rule) => rule.rule(session).await,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Matchers {
     pub inner: Vec<Matcher>,
-    pub config: RwLock<Arc<MatchersConfig>>,
+    pub config: Arc<ConfigStore>,
     temps: TempMatchers,
+    states: Arc<States>,
+    globals: Vec<GlobalLayer>,
+    filter: EventFilter,
+    arbiter: Arbiter,
+    impls: ImplCache,
+    bot_names: Arc<BotNames>,
+    suggester: Suggester,
     hooks: Vec<Box<dyn MatchersHook + Send + 'static>>,
     ob: RwLock<Option<Arc<dyn ActionCaller + Send + 'static>>>,
 }
 
 impl Matchers {
     pub fn add_matcher(mut self, matcher: Matcher) -> Self {
+        self.suggester.register(matcher.commands());
         self.inner.push(matcher);
         self
     }
-    async fn temp_call(
+    /// 注册 `#[walle::plugin]` 插件中的所有 handler
+    pub fn add_plugin<P: Plugin>(self, plugin: P) -> Self {
+        Arc::new(plugin)
+            .matchers()
+            .into_iter()
+            .fold(self, Self::add_matcher)
+    }
+    /// 注册全局 pre-handler，每个 event 在所有 matcher（包括临时 matcher）之前执行一次
+    ///
+    /// 不匹配时该 event 将被忽略，写入的 extensions 对所有 matcher 可见。
+    pub fn with_global_pre_handler<PH>(mut self, pre_handler: PH) -> Self
+    where
+        PH: PreHandler + Send + Sync + 'static,
+    {
+        self.globals
+            .push(GlobalLayer::PreHandler(Box::new(pre_handler)));
+        self
+    }
+    /// 注册全局 rule，每个 event 在所有 matcher（包括临时 matcher）之前执行一次
+    pub fn with_global_rule<R>(mut self, rule: R) -> Self
+    where
+        R: Rule + Send + Sync + 'static,
+    {
+        self.globals.push(GlobalLayer::Rule(Box::new(rule)));
+        self
+    }
+    /// 注册在启动与关闭时执行的 hook，如需持久化的 `builtin::Quota`
+    pub fn add_hook<H: MatchersHook + Send + 'static>(mut self, hook: H) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+    /// 注册共享状态，可通过 `State<T>` extractor 或 `Session::state` 获取
+    pub fn with_state<T: Send + Sync + 'static>(mut self, state: T) -> Self {
+        Arc::make_mut(&mut self.states).insert(state);
+        self
+    }
+    /// 注册插件配置，启动、重新加载与运行时修改配置时将校验该配置段
+    pub fn plugin_config<T: PluginSection>(self) -> Self {
+        self.config
+            .add_validator(Box::new(|config| config.plugin::<T>().map(|_| ())));
+        self
+    }
+    /// 从 `config_path` 重新读取配置，校验失败时保留当前配置
+    pub async fn reload_config(&self) -> WalleResult<()> {
+        let config = self.config.load()?;
+        self.config.validate(&config)?;
+        self.config.set(config).await;
+        Ok(())
+    }
+    /// 运行时对配置的修改将写回 `path`
+    pub fn config_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.config = Arc::new(self.config.with_path(path.into()));
+        self
+    }
+    /// 将用户加入全局黑名单并写回配置文件，指定 bot 的名单请使用 `ConfigStore::update`
+    pub async fn block_user(&self, user_id: &str) -> WalleResult<()> {
+        self.config
+            .update(|c| {
+                c.access.blocklist.add_user(user_id);
+            })
+            .await
+    }
+    /// 将用户移出全局黑名单
+    pub async fn unblock_user(&self, user_id: &str) -> WalleResult<()> {
+        self.config
+            .update(|c| {
+                c.access.blocklist.remove_user(user_id);
+            })
+            .await
+    }
+    /// 将群组加入全局黑名单
+    pub async fn block_group(&self, group_id: &str) -> WalleResult<()> {
+        self.config
+            .update(|c| {
+                c.access.blocklist.add_group(group_id);
+            })
+            .await
+    }
+    /// 将群组移出全局黑名单
+    pub async fn unblock_group(&self, group_id: &str) -> WalleResult<()> {
+        self.config
+            .update(|c| {
+                c.access.blocklist.remove_group(group_id);
+            })
+            .await
+    }
+    /// 将用户加入全局白名单
+    pub async fn allow_user(&self, user_id: &str) -> WalleResult<()> {
+        self.config
+            .update(|c| {
+                c.access.allowlist.add_user(user_id);
+            })
+            .await
+    }
+    /// 将用户移出全局白名单
+    pub async fn disallow_user(&self, user_id: &str) -> WalleResult<()> {
+        self.config
+            .update(|c| {
+                c.access.allowlist.remove_user(user_id);
+            })
+            .await
+    }
+    /// 将群组加入全局白名单
+    pub async fn allow_group(&self, group_id: &str) -> WalleResult<()> {
+        self.config
+            .update(|c| {
+                c.access.allowlist.add_group(group_id);
+            })
+            .await
+    }
+    /// 将群组移出全局白名单
+    pub async fn disallow_group(&self, group_id: &str) -> WalleResult<()> {
+        self.config
+            .update(|c| {
+                c.access.allowlist.remove_group(group_id);
+            })
+            .await
+    }
+    /// 在后台重新获取所有 bot 的隐式昵称
+    async fn refresh_bot_names(&self) {
+        let Some(ob) = self.ob.read().await.clone() else {
+            return;
+        };
+        let config = self.config.get().await;
+        if config.self_info_nicknames || config.group_card_nicknames {
+            self.bot_names.refresh(ob);
+        }
+    }
+    pub(crate) async fn dispatch(&self, mut event: Event) -> WalleResult<()> {
+        use walle_core::alt::ColoredAlt;
+        if event.ty.as_str() == "meta" {
+            // status_update 会周期性发送，之后上线的 bot 由 `BotNames::for_session` 按需获取
+            if event.detail_type == "connect" {
+                self.refresh_bot_names().await;
+            }
+            return Ok(());
+        }
+        let config = self.config.get().await;
+        if self.filter.drop_event(&config.filter, &event) {
+            return Ok(());
+        }
+        if !config.access_allowed(&event) {
+            debug!(target: "Walle", "event {} blocked by access list", event.id);
+            return Ok(());
+        }
+        // 被黑白名单拦截的 bot 不参与选举
+        if let Some(arbitration) = &config.arbitration {
+            if !self.arbiter.won(arbitration, &event).await {
+                debug!(target: "Walle", "event {} handled by another bot", event.id);
+                return Ok(());
+            }
+        }
+        info!(target: "Walle", "{}", event.colored_alt());
+        let ob: Arc<dyn ActionCaller + Send + 'static> =
+            self.ob.read().await.clone().ok_or(WalleError::NotStarted)?;
+        if let Some(normalize) = &config.normalize {
+            normalize.apply(&mut event);
+        }
+        let mut session = Session::new(
+            event,
+            ob,
+            config,
+            self.config.clone(),
+            self.temps.clone(),
+            self.states.clone(),
+        )
+        .with_impl_cache(self.impls.clone());
+        if session.event.ty == "message" {
+            if let Some(names) = self.bot_names.for_session(&session) {
+                session.extensions.insert(names);
+            }
+        }
+        for global in &self.globals {
+            if global.apply(&mut session).await == Signal::NotMatch {
+                debug!(target: "Walle", "event {} blocked by global layer", session.event.id);
+                return Ok(());
+            }
+        }
+        // 全局 rule 没有 handler 阶段，其登记的额度在此扣除，对每个 event 只扣除一次
+        if let Err(reply) = session.charge() {
+            if let Some(reply) = reply {
+                if let Err(e) = session.reply(reply).await {
+                    warn!(target: "Walle", "reply limit rejection failed: {}", e);
+                }
+            }
+            return Ok(());
+        }
+        if self.temp_call(&session).await {
+            return Ok(());
+        }
+        let failure = super::UsageFailure::default();
+        session.extensions.insert(failure.clone());
+        let mut matched = false;
+        for matcher in &self.inner {
+            let fork = session.fork();
+            let signal = matcher.handle(fork.clone()).await;
+            if signal != Signal::NotMatch && fork.settle_charges() {
+                warn!(target: "Walle", "limit rule charged after a handler not run by run_handler");
+            }
+            match signal {
+                Signal::MatchAndBlock => return Ok(()),
+                Signal::Matched => matched = true,
+                Signal::NotMatch => {}
+            }
+        }
+        if matched {
+            return Ok(());
+        }
+        if let Some(usage) = failure.0.get() {
+            let reply = &session.config.usage_reply;
+            if !reply.is_empty() {
+                if let Err(e) = session.reply(reply.replace("{usage}", usage)).await {
+                    warn!(target: "Walle", "reply command usage failed: {}", e);
+                }
+            }
+            return Ok(());
+        }
+        if let Some(suggestion) = self.suggester.suggest(&session.config, &session.event) {
+            if let Err(e) = session.reply(suggestion).await {
+                warn!(target: "Walle", "reply command suggestion failed: {}", e);
+            }
+        }
+        Ok(())
+    }
+    #[cfg(test)]
+    pub(crate) async fn mock_start(
         &self,
-        event: &Event,
-        config: &Arc<MatchersConfig>,
-        ob: &Arc<dyn ActionCaller + Send + 'static>,
-    ) -> bool {
+        caller: Arc<super::mock::MockCaller>,
+        config: MatchersConfig,
+    ) {
+        *self.ob.write().await = Some(caller);
+        self.config.set(config).await;
+    }
+    async fn temp_call(&self, session: &Session) -> bool {
         let mut matched_temp_key: Option<String> = None;
         let mut temps = self.temps.lock().await;
         for temp in temps.iter() {
-            let session = Session::new(
-                event.clone(),
-                ob.clone(),
-                config.clone(),
-                self.temps.clone(),
-            );
-            if temp.1.handle(session).await != Signal::NotMatch {
+            if temp.1.handle(session.fork()).await != Signal::NotMatch {
                 matched_temp_key = Some(temp.0.to_owned());
                 break;
             }
@@ -73,12 +322,20 @@ impl EventHandler<Event, Action, Resp> for Matchers {
         AH: ActionHandler<Event, Action, Resp> + Send + Sync + 'static,
         EH: EventHandler<Event, Action, Resp> + Send + Sync + 'static,
     {
+        if let Err(e) = self.config.validate(&config) {
+            error!(target: "Walle", "invalid plugin config:\n{}", e);
+            return Err(e);
+        }
+        if config.suggest.is_some() && config.command_start.iter().all(String::is_empty) {
+            warn!(target: "Walle", "command suggestion requires a non-empty command_start");
+        }
         *self.ob.write().await = Some(Arc::new(ob.clone()));
-        *self.config.write().await = Arc::new(config);
+        self.config.set(config).await;
         let ob = self.ob.read().await.clone().unwrap();
         for hook in self.hooks.iter() {
             hook.on_start(&ob).await
         }
+        self.refresh_bot_names().await;
         Ok(vec![])
     }
     async fn call<AH, EH>(&self, event: Event, _: &Arc<OneBot<AH, EH>>) -> WalleResult<()>
@@ -86,29 +343,7 @@ impl EventHandler<Event, Action, Resp> for Matchers {
         AH: ActionHandler<Event, Action, Resp> + Send + Sync + 'static,
         EH: EventHandler<Event, Action, Resp> + Send + Sync + 'static,
     {
-        use walle_core::alt::ColoredAlt;
-        if event.ty.as_str() == "meta" {
-            return Ok(());
-        }
-        info!(target: "Walle", "{}", event.colored_alt());
-        let ob: Arc<dyn ActionCaller + Send + 'static> =
-            self.ob.read().await.clone().ok_or(WalleError::NotStarted)?;
-        let config = self.config.read().await.clone();
-        if self.temp_call(&event, &config, &ob).await {
-            return Ok(());
-        }
-        for matcher in &self.inner {
-            let session = Session::new(
-                event.clone(),
-                ob.clone(),
-                config.clone(),
-                self.temps.clone(),
-            );
-            if matcher.handle(session).await == Signal::MatchAndBlock {
-                return Ok(());
-            }
-        }
-        Ok(())
+        self.dispatch(event).await
     }
     async fn shutdown(&self) {
         let ob = self.ob.read().await.clone().unwrap();
@@ -118,3 +353,93 @@ impl EventHandler<Event, Action, Resp> for Matchers {
         *self.ob.write().await = None;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::Matchers;
+    use crate::matcher::mock::{dispatch, group_message, MockCaller};
+    use crate::{matcher, pre_handle_fn, rule_fn, MatcherHandler, Session, Signal};
+    use std::sync::{Arc, Mutex};
+    use walle_core::util::ValueMapExt;
+
+    #[derive(Clone)]
+    struct Marker(String);
+
+    fn marker(session: &Session) -> String {
+        session
+            .extensions
+            .get::<Marker>()
+            .map(|m| m.0)
+            .unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn global_layers() {
+        let log = Arc::new(Mutex::new(Vec::<String>::new()));
+        let (l1, l2, l3) = (log.clone(), log.clone(), log.clone());
+        let matchers = Matchers::default()
+            .with_global_pre_handler(pre_handle_fn(move |s: &mut Session| {
+                let text = s.event.extra.get_downcast::<String>("alt_message").unwrap();
+                l1.lock().unwrap().push(format!("global:{}", text));
+                s.extensions.insert(Marker(text));
+                Signal::Matched
+            }))
+            .with_global_rule(rule_fn(|s: &Session| {
+                if marker(s) == "reject" {
+                    Signal::NotMatch
+                } else {
+                    Signal::Matched
+                }
+            }))
+            .add_matcher(
+                matcher(move |s: Session| {
+                    let log = l2.clone();
+                    async move { log.lock().unwrap().push(format!("matcher:{}", marker(&s))) }
+                })
+                .awaited()
+                .boxed(),
+            )
+            .add_matcher(
+                matcher(move |s: Session| {
+                    let log = l3.clone();
+                    async move { log.lock().unwrap().push(format!("matcher:{}", marker(&s))) }
+                })
+                .awaited()
+                .boxed(),
+            );
+        let l4 = log.clone();
+        matchers.temps.lock().await.insert(
+            "temp".to_owned(),
+            matcher(move |s: Session| {
+                let log = l4.clone();
+                async move {
+                    log.lock().unwrap().push(format!("temp:{}", marker(&s)));
+                    Signal::NotMatch
+                }
+            })
+            .awaited()
+            .boxed(),
+        );
+        let events = ["hi", "reject"]
+            .into_iter()
+            .map(|text| group_message("user", text))
+            .collect();
+        dispatch(
+            &matchers,
+            Arc::new(MockCaller::default()),
+            Default::default(),
+            events,
+        )
+        .await;
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "global:hi",
+                "temp:hi",
+                "matcher:hi",
+                "matcher:hi",
+                "global:reject"
+            ]
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! 测试用的 ActionCaller 与 event 构造
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+use walle_core::{
+    action::Action,
+    event::Event,
+    prelude::{async_trait, GetSelfs},
+    resp::Resp,
+    structs::Selft,
+    util::{Value, ValueMapExt},
+    value_map, WalleResult,
+};
+
+use crate::{ActionCaller, Bot, Matchers, MatchersConfig, Session};
+
+type Respond = Box<dyn Fn(&Action) -> Option<Resp> + Send + Sync>;
+
+/// 记录所有 action 的 ActionCaller，`send_message` 总是成功
+#[derive(Default)]
+pub(crate) struct MockCaller {
+    actions: Mutex<Vec<Action>>,
+    respond: Option<Respond>,
+    /// `get_impl` 的返回值，默认为 `mock`
+    implt: Option<String>,
+    impl_calls: AtomicUsize,
+}
+
+impl MockCaller {
+    /// 使用 `respond` 响应 `send_message` 以外的 action
+    pub(crate) fn respond<F>(respond: F) -> Self
+    where
+        F: Fn(&Action) -> Option<Resp> + Send + Sync + 'static,
+    {
+        Self {
+            respond: Some(Box::new(respond)),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn with_impl(self, implt: &str) -> Self {
+        Self {
+            implt: Some(implt.to_owned()),
+            ..self
+        }
+    }
+
+    /// `get_impl` 被调用的次数
+    pub(crate) fn impl_calls(&self) -> usize {
+        self.impl_calls.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn actions(&self) -> Vec<String> {
+        let actions = self.actions.lock().unwrap();
+        actions.iter().map(|a| a.action.clone()).collect()
+    }
+
+    /// 所有 `send_message` 的纯文本内容
+    pub(crate) fn sent(&self) -> Vec<String> {
+        let actions = self.actions.lock().unwrap();
+        actions
+            .iter()
+            .filter(|a| a.action == "send_message")
+            .filter_map(|a| a.params.try_get_as_ref::<&Vec<Value>>("message").ok())
+            .map(|segs| {
+                segs.iter()
+                    .filter_map(|seg| {
+                        let seg = seg.try_as_ref::<&walle_core::util::ValueMap>().ok()?;
+                        let data = seg.try_get_as_ref::<&walle_core::util::ValueMap>("data");
+                        data.ok()?.try_get_as_ref::<&str>("text").ok()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl GetSelfs for MockCaller {
+    async fn get_selfs(&self) -> Vec<Selft> {
+        vec![selft("bot")]
+    }
+    async fn get_impl(&self, _: &Selft) -> String {
+        self.impl_calls.fetch_add(1, Ordering::Relaxed);
+        self.implt.clone().unwrap_or_else(|| "mock".to_owned())
+    }
+}
+
+#[async_trait]
+impl ActionCaller for MockCaller {
+    async fn call_action(&self, action: Action) -> WalleResult<Resp> {
+        let resp = if action.action == "send_message" {
+            Some(Resp::ok(value_map! { "message_id": "0", "time": 0.0 }, ""))
+        } else {
+            self.respond.as_ref().and_then(|respond| respond(&action))
+        };
+        self.actions.lock().unwrap().push(action);
+        Ok(resp.unwrap_or_else(|| Resp::failed(10002, Value::Null, "unsupported action")))
+    }
+    async fn get_bots(&self) -> Vec<Bot> {
+        vec![]
+    }
+}
+
+pub(crate) fn selft(user_id: &str) -> Selft {
+    Selft {
+        platform: "qq".to_owned(),
+        user_id: user_id.to_owned(),
+    }
+}
+
+/// bot `bot` 收到的群消息 event，`text` 为唯一的 text 消息段
+pub(crate) fn group_message(user_id: &str, text: &str) -> Event {
+    Event {
+        id: String::default(),
+        time: 0.0,
+        ty: "message".to_owned(),
+        detail_type: "group".to_owned(),
+        sub_type: String::default(),
+        extra: value_map! {
+            "self": value_map! { "platform": "qq", "user_id": "bot" },
+            "message_id": "1",
+            "message": vec![text_seg(text)],
+            "alt_message": text,
+            "user_id": user_id,
+            "group_id": "group"
+        },
+    }
+}
+
+/// bot `bot` 收到的群消息 event
+pub(crate) fn bot_message(bot: &str, text: &str) -> Event {
+    let mut event = group_message("user", text);
+    let selft = value_map! { "platform": "qq", "user_id": bot };
+    event.extra.insert("self".to_owned(), Value::Map(selft));
+    event
+}
+
+/// text 消息段
+pub(crate) fn text_seg(text: &str) -> Value {
+    Value::Map(value_map! { "type": "text", "data": value_map! { "text": text } })
+}
+
+/// mention 消息段
+pub(crate) fn mention_seg(user_id: &str) -> Value {
+    Value::Map(value_map! { "type": "mention", "data": value_map! { "user_id": user_id } })
+}
+
+/// 替换 event 的消息段
+pub(crate) fn with_message(mut event: Event, message: Vec<Value>) -> Event {
+    event
+        .extra
+        .insert("message".to_owned(), Value::List(message));
+    event
+}
+
+pub(crate) fn session(event: Event, caller: Arc<MockCaller>, config: MatchersConfig) -> Session {
+    Session::new(
+        event,
+        caller,
+        Arc::new(config),
+        Arc::default(),
+        Arc::default(),
+        Arc::default(),
+    )
+}
+
+/// 本次测试独有的临时文件路径
+pub(crate) fn temp_path(name: &str) -> std::path::PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir().join(format!("walle_{}_{}_{}", std::process::id(), n, name))
+}
+
+/// 以 `caller` 启动 matchers 并依次处理 events
+pub(crate) async fn dispatch(
+    matchers: &Matchers,
+    caller: Arc<MockCaller>,
+    config: MatchersConfig,
+    events: Vec<Event>,
+) {
+    matchers.mock_start(caller, config).await;
+    for event in events {
+        matchers.dispatch(event).await.unwrap();
+    }
+}
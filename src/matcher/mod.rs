@@ -1,18 +1,33 @@
 use walle_core::prelude::{async_trait, Event};
 
+mod arbiter;
+mod charge;
+mod execution;
+mod extension;
+mod filter;
 mod handle;
 mod hook;
 mod matchers;
+#[cfg(test)]
+pub(crate) mod mock;
+mod nickname;
 mod pre_handle;
 mod rule;
 mod session;
+mod state;
+mod suggest;
 
+pub(crate) use charge::Charge;
+pub use execution::{Execution, ExecutionPolicy};
+pub use extension::*;
 pub use handle::*;
 pub use hook::*;
 pub use matchers::*;
+pub use nickname::BotNicknames;
 pub use pre_handle::*;
 pub use rule::*;
 pub use session::*;
+pub use state::*;
 
 struct TempMatcher {
     pub tx: tokio::sync::mpsc::UnboundedSender<Event>,
@@ -21,6 +36,12 @@ struct TempMatcher {
 #[async_trait]
 impl MatcherHandler for TempMatcher {
     async fn handle(&self, session: Session) -> Signal {
+        if let Err(reply) = session.charge() {
+            if let Some(reply) = reply {
+                session.reply(reply).await.ok();
+            }
+            return Signal::NotMatch;
+        }
         self.tx.send(session.event).ok();
         Signal::MatchAndBlock
     }
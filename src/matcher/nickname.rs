@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use walle_core::{structs::Selft, util::ValueMapExt};
+
+use crate::{ActionCaller, ActionCallerExt, Bot, Session};
+
+/// 当前 bot 的隐式昵称，来自 `get_self_info` 与群名片
+///
+/// 由 `Matchers` 写入消息事件的 session extensions，与 `MatchersConfig::nicknames` 一同生效。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BotNicknames(pub Vec<String>);
+
+/// 以 bot 为键缓存的昵称
+#[derive(Debug, Default)]
+pub(crate) struct BotNames {
+    selfs: DashMap<Selft, Vec<String>>,
+    cards: DashMap<(Selft, String), Option<String>>,
+}
+
+fn push_name(names: &mut Vec<String>, name: String) {
+    if !name.is_empty() && !names.contains(&name) {
+        names.push(name);
+    }
+}
+
+impl BotNames {
+    async fn fetch_self(&self, bot: &Bot) {
+        let mut names = vec![];
+        match bot.get_self_info().await {
+            Ok(info) => {
+                push_name(&mut names, info.user_name);
+                push_name(&mut names, info.user_displayname);
+            }
+            Err(e) => {
+                tracing::debug!(target: "Walle", "get self info of {} failed: {}", bot.selft.user_id, e)
+            }
+        }
+        self.selfs.insert(bot.selft.clone(), names);
+    }
+
+    async fn fetch_card(&self, bot: &Bot, group_id: String) {
+        let card = match bot
+            .get_group_member_info(group_id.clone(), bot.selft.user_id.clone())
+            .await
+        {
+            Ok(info) => Some(info.user_displayname).filter(|s| !s.is_empty()),
+            Err(e) => {
+                tracing::debug!(target: "Walle", "get group card in {} failed: {}", group_id, e);
+                None
+            }
+        };
+        self.cards.insert((bot.selft.clone(), group_id), card);
+    }
+
+    /// 连接或重连后在后台重新获取所有 bot 的昵称，并清除群名片缓存
+    pub(crate) fn refresh(self: &Arc<Self>, caller: Arc<dyn ActionCaller + Send + 'static>) {
+        let names = self.clone();
+        tokio::spawn(async move {
+            for bot in caller.get_bots().await {
+                names.cards.retain(|(selft, _), _| selft != &bot.selft);
+                names.fetch_self(&bot).await;
+            }
+        });
+    }
+
+    /// 消息事件对应 bot 的隐式昵称
+    ///
+    /// 只读取缓存，未缓存时先写入空值并在后台获取，不阻塞事件处理。
+    pub(crate) fn for_session(self: &Arc<Self>, session: &Session) -> Option<BotNicknames> {
+        let config = &session.config;
+        if !config.self_info_nicknames && !config.group_card_nicknames {
+            return None;
+        }
+        let bot = Bot {
+            selft: session.selft.clone()?,
+            caller: session.caller.clone(),
+        };
+        let mut names = vec![];
+        if config.self_info_nicknames {
+            match self.selfs.get(&bot.selft).map(|names| names.clone()) {
+                Some(cached) => names = cached,
+                None => {
+                    self.selfs.insert(bot.selft.clone(), vec![]);
+                    let (bot_names, bot) = (self.clone(), bot.clone());
+                    tokio::spawn(async move { bot_names.fetch_self(&bot).await });
+                }
+            }
+        }
+        if config.group_card_nicknames {
+            if let Ok(group_id) = session.event.extra.get_downcast::<String>("group_id") {
+                let key = (bot.selft.clone(), group_id);
+                match self.cards.get(&key).map(|card| card.clone()) {
+                    Some(Some(card)) => push_name(&mut names, card),
+                    Some(None) => {}
+                    None => {
+                        self.cards.insert(key.clone(), None);
+                        let bot_names = self.clone();
+                        tokio::spawn(async move { bot_names.fetch_card(&bot, key.1).await });
+                    }
+                }
+            }
+        }
+        Some(BotNicknames(names))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use walle_core::{resp::Resp, value_map};
+
+    use super::{BotNames, BotNicknames};
+    use crate::matcher::mock::{group_message, session, MockCaller};
+    use crate::MatchersConfig;
+
+    #[tokio::test]
+    async fn fetch_in_background() {
+        let caller = Arc::new(MockCaller::respond(|action| {
+            (action.action == "get_self_info").then(|| {
+                let info = value_map! {
+                    "user_id": "bot",
+                    "user_name": "walle",
+                    "user_displayname": "",
+                    "user_remark": ""
+                };
+                Resp::ok(info, "")
+            })
+        }));
+        let config = MatchersConfig {
+            self_info_nicknames: true,
+            ..Default::default()
+        };
+        let session = session(group_message("user", "hi"), caller.clone(), config);
+        let names = Arc::new(BotNames::default());
+        assert_eq!(names.for_session(&session), Some(BotNicknames(vec![])));
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(
+            names.for_session(&session),
+            Some(BotNicknames(vec!["walle".to_owned()]))
+        );
+        assert_eq!(caller.actions(), vec!["get_self_info"]);
+    }
+}
@@ -2,8 +2,9 @@ use crate::{MatcherHandler, Rule, Session, Signal};
 
 use walle_core::{prelude::async_trait, WalleResult};
 
+#[async_trait]
 pub trait PreHandler {
-    fn pre_handle(&self, session: &mut Session) -> Signal;
+    async fn pre_handle(&self, session: &mut Session) -> Signal;
     fn layer<H>(self, handler: H) -> LayeredPreHandler<Self, H>
     where
         Self: Sized,
@@ -27,8 +28,9 @@ pub trait PreHandler {
     }
 }
 
+#[async_trait]
 impl PreHandler for () {
-    fn pre_handle(&self, _: &mut Session) -> Signal {
+    async fn pre_handle(&self, _: &mut Session) -> Signal {
         Signal::Matched
     }
 }
@@ -45,57 +47,77 @@ where
     H: MatcherHandler + Send + Sync,
 {
     async fn handle(&self, mut session: Session) -> Signal {
-        let mut sig = self.pre.pre_handle(&mut session);
+        let mut sig = self.pre.pre_handle(&mut session).await;
         if sig != Signal::NotMatch {
-            sig = self.handler.handle(session).await & sig;
+            // handler 的 MatchAndBlock 不因 rule 的 Matched 而降级
+            sig = match self.handler.handle(session).await {
+                Signal::NotMatch => Signal::NotMatch,
+                handled => handled | sig,
+            };
         }
         sig
     }
+    fn commands(&self) -> Vec<String> {
+        self.handler.commands()
+    }
 }
 
 pub struct JoinedPreHandler<PR0, PR1>(pub PR0, pub PR1);
 
+#[async_trait]
 impl<PR0, PR1> PreHandler for JoinedPreHandler<PR0, PR1>
 where
     PR0: PreHandler + Sync,
     PR1: PreHandler + Sync,
 {
-    fn pre_handle(&self, session: &mut Session) -> Signal {
-        self.0.pre_handle(session) & self.1.pre_handle(session)
+    async fn pre_handle(&self, session: &mut Session) -> Signal {
+        match self.0.pre_handle(session).await {
+            Signal::NotMatch => Signal::NotMatch,
+            sig => sig & self.1.pre_handle(session).await,
+        }
     }
 }
 
 pub struct JoinedPreHandlerRule<PH, R>(pub PH, pub R);
 
+#[async_trait]
 impl<PH, R> PreHandler for JoinedPreHandlerRule<PH, R>
 where
-    PH: PreHandler,
-    R: Rule,
+    PH: PreHandler + Sync,
+    R: Rule + Sync,
 {
-    fn pre_handle(&self, session: &mut Session) -> Signal {
-        self.0.pre_handle(session) & self.1.rule(session)
+    async fn pre_handle(&self, session: &mut Session) -> Signal {
+        match self.0.pre_handle(session).await {
+            Signal::NotMatch => Signal::NotMatch,
+            sig => sig & self.1.rule(session).await,
+        }
     }
 }
 
 pub struct JoinedRulePreHandler<R, PH>(pub R, pub PH);
 
+#[async_trait]
 impl<R, PH> PreHandler for JoinedRulePreHandler<R, PH>
 where
     R: Rule + Sync,
     PH: PreHandler + Sync,
 {
-    fn pre_handle(&self, session: &mut Session) -> Signal {
-        self.0.rule(session) & self.1.pre_handle(session)
+    async fn pre_handle(&self, session: &mut Session) -> Signal {
+        match self.0.rule(session).await {
+            Signal::NotMatch => Signal::NotMatch,
+            sig => sig & self.1.pre_handle(session).await,
+        }
     }
 }
 
 pub struct PreHandleFn<F>(F);
 
+#[async_trait]
 impl<F> PreHandler for PreHandleFn<F>
 where
     F: Fn(&mut Session) -> Signal + Sync,
 {
-    fn pre_handle(&self, session: &mut Session) -> Signal {
+    async fn pre_handle(&self, session: &mut Session) -> Signal {
         self.0(session)
     }
 }
@@ -109,11 +131,12 @@ where
 
 pub struct PreHandleFnUnwarp<F>(F);
 
+#[async_trait]
 impl<F> PreHandler for PreHandleFnUnwarp<F>
 where
     F: Fn(&mut Session) -> WalleResult<Signal> + Sync,
 {
-    fn pre_handle(&self, session: &mut Session) -> Signal {
+    async fn pre_handle(&self, session: &mut Session) -> Signal {
         self.0(session).into()
     }
 }
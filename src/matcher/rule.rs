@@ -2,8 +2,9 @@ use crate::{JoinedRulePreHandler, MatcherHandler, PreHandler, Session, Signal};
 
 use walle_core::{prelude::async_trait, WalleResult};
 
+#[async_trait]
 pub trait Rule {
-    fn rule(&self, session: &Session) -> Signal;
+    async fn rule(&self, session: &Session) -> Signal;
     fn layer<H>(self, handler: H) -> LayeredRule<Self, H>
     where
         Self: Sized,
@@ -30,8 +31,9 @@ pub trait Rule {
     }
 }
 
+#[async_trait]
 impl Rule for () {
-    fn rule(&self, _: &Session) -> Signal {
+    async fn rule(&self, _: &Session) -> Signal {
         Signal::Matched
     }
 }
@@ -48,33 +50,45 @@ where
     H: MatcherHandler + Send + Sync,
 {
     async fn handle(&self, session: Session) -> Signal {
-        let mut sig = self.rule.rule(&session);
+        let mut sig = self.rule.rule(&session).await;
         if sig != Signal::NotMatch {
-            sig = self.handler.handle(session).await & sig
+            // handler 的 MatchAndBlock 不因 rule 的 Matched 而降级
+            sig = match self.handler.handle(session).await {
+                Signal::NotMatch => Signal::NotMatch,
+                handled => handled | sig,
+            };
         }
         sig
     }
+    fn commands(&self) -> Vec<String> {
+        self.handler.commands()
+    }
 }
 
 pub struct JoinedRule<R0, R1>(pub R0, pub R1);
 
+#[async_trait]
 impl<R0, R1> Rule for JoinedRule<R0, R1>
 where
     R0: Rule + Send + Sync,
     R1: Rule + Send + Sync,
 {
-    fn rule(&self, session: &Session) -> Signal {
-        self.0.rule(session) & self.1.rule(session)
+    async fn rule(&self, session: &Session) -> Signal {
+        match self.0.rule(session).await {
+            Signal::NotMatch => Signal::NotMatch,
+            sig => sig & self.1.rule(session).await,
+        }
     }
 }
 
 pub struct RuleFn<F>(F);
 
+#[async_trait]
 impl<F> Rule for RuleFn<F>
 where
     F: Fn(&Session) -> Signal + Send + Sync,
 {
-    fn rule(&self, session: &Session) -> Signal {
+    async fn rule(&self, session: &Session) -> Signal {
         self.0(session)
     }
 }
@@ -95,11 +109,12 @@ where
     RuleFnUnwarp(rule)
 }
 
+#[async_trait]
 impl<F> Rule for RuleFnUnwarp<F>
 where
     F: Fn(&Session) -> WalleResult<Signal> + Send + Sync,
 {
-    fn rule(&self, session: &Session) -> Signal {
+    async fn rule(&self, session: &Session) -> Signal {
         self.0(session).into()
     }
 }
@@ -1,14 +1,20 @@
 use super::TempMatcher;
 use crate::{
-    ActionCaller, ActionCallerExt, MatcherHandler, MatchersConfig, PreHandler, Rule, TempMatchers,
+    ActionCaller, ActionCallerExt, BotNicknames, ConfigStore, Extensions, MatcherHandler,
+    MatchersConfig, PreHandler, Rule, States, TempMatchers,
+};
+use dashmap::DashMap;
+use std::{
+    pin::Pin,
+    sync::{Arc, OnceLock},
+    time::Duration,
 };
-use std::{pin::Pin, sync::Arc, time::Duration};
 use walle_core::{
     event::{
         BaseEvent, DetailTypeLevel, ImplLevel, ParseEvent, PlatformLevel, SubTypeLevel,
         TryFromEvent, TypeLevel,
     },
-    prelude::{async_trait, Event},
+    prelude::{async_trait, Event, GetSelfs},
     segment::{IntoMessage, MsgSegment, Segments},
     structs::{Selft, SendMessageResp},
     util::{Value, ValueMap, ValueMapExt},
@@ -21,17 +27,28 @@ pub struct Session {
     pub event: Event,
     pub config: Arc<MatchersConfig>,
     pub caller: Arc<dyn ActionCaller + Send + 'static>,
+    /// pre-handler 与 rule 计算的数据，见 `Ext<T>`
+    pub extensions: Extensions,
+    config_store: Arc<ConfigStore>,
     reply_sign: ReplySign,
     temps: TempMatchers,
+    states: Arc<States>,
+    command: Arc<OnceLock<String>>,
+    impls: ImplCache,
     pub(crate) selft: Option<Selft>,
 }
 
+/// 以 bot 为键缓存 `get_impl` 结果
+pub(crate) type ImplCache = Arc<DashMap<Selft, String>>;
+
 impl Session {
     pub fn new(
         event: Event,
         caller: Arc<dyn ActionCaller + Send + 'static>,
         config: Arc<MatchersConfig>,
+        config_store: Arc<ConfigStore>,
         temps: TempMatchers,
+        states: Arc<States>,
     ) -> Self {
         let reply_sign = ReplySign::new(&event);
         Self {
@@ -39,9 +56,74 @@ impl Session {
             event,
             config,
             caller,
+            extensions: Extensions::default(),
+            config_store,
             reply_sign,
             temps,
+            states,
+            command: Arc::default(),
+            impls: ImplCache::default(),
+        }
+    }
+
+    pub(crate) fn with_impl_cache(self, impls: ImplCache) -> Self {
+        Self { impls, ..self }
+    }
+
+    /// 当前 bot 的实现名称，无 self 信息时为空字符串
+    ///
+    /// 结果按 bot 缓存，同一 `Matchers` 下的所有 session 共享。
+    pub async fn implt(&self) -> String {
+        let Some(selft) = &self.selft else {
+            return String::default();
+        };
+        if let Some(implt) = self.impls.get(selft) {
+            return implt.clone();
+        }
+        let implt = self.get_impl(selft).await;
+        if !implt.is_empty() {
+            self.impls.insert(selft.clone(), implt.clone());
+        }
+        implt
+    }
+
+    /// 获取 `Matchers::with_state` 注册的共享状态
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.states.get()
+    }
+
+    /// `MatchersConfig::nicknames` 与当前 bot 的隐式昵称，见 `BotNicknames`
+    pub fn nicknames(&self) -> Vec<String> {
+        let mut nicknames = self.config.nicknames.clone();
+        if let Some(BotNicknames(names)) = self.extensions.get() {
+            for name in names {
+                if !nicknames.contains(&name) {
+                    nicknames.push(name);
+                }
+            }
         }
+        nicknames
+    }
+
+    /// 复制 session 供单个 matcher 使用，extensions 独立且命令未被认领
+    pub(crate) fn fork(&self) -> Self {
+        Self {
+            extensions: self.extensions.fork(),
+            command: Arc::default(),
+            ..self.clone()
+        }
+    }
+
+    /// 标记事件已被命令认领，此后 extractor 失败将视为输入有误，没有 matcher 处理该事件时回复命令用法
+    ///
+    /// 仅第一次认领生效，`usage` 为命令用法说明。
+    pub fn claim_command(&self, usage: String) {
+        self.command.get_or_init(|| usage);
+    }
+
+    /// 认领该事件的命令用法说明
+    pub fn claimed_command(&self) -> Option<&str> {
+        self.command.get().map(String::as_str)
     }
 }
 
@@ -140,6 +222,15 @@ impl Session {
             ReplySign::UnReplyAble => Err(WalleError::Other("unreplyable session".to_string())),
         }
     }
+    /// 修改 Matchers 配置，设置了 `Matchers::config_path` 时将写回文件
+    ///
+    /// 当前 session 的 `config` 不会随之更新
+    pub async fn update_config<F>(&self, f: F) -> WalleResult<()>
+    where
+        F: FnOnce(&mut MatchersConfig),
+    {
+        self.config_store.update(f).await
+    }
     pub fn getter<'a>(&'a mut self) -> SessionGetter<'a, (), ()> {
         SessionGetter {
             session: self,
@@ -265,11 +356,38 @@ where
 #[async_trait]
 pub trait FromSessionPart: Sized {
     async fn from_session_part(session: &mut Session) -> WalleResult<Self>;
+    /// 命令 extractor 的命令名与别名，注册时收集用于命令提示
+    fn commands() -> Vec<String> {
+        vec![]
+    }
 }
 
 #[async_trait]
 pub trait FromSession: Sized {
     async fn from_session(session: Session) -> WalleResult<Self>;
+    fn commands() -> Vec<String> {
+        vec![]
+    }
+}
+
+/// 提取失败时为 None，不会导致整个 handler 不匹配
+#[async_trait]
+impl<T: FromSessionPart + Send> FromSessionPart for Option<T> {
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        Ok(T::from_session_part(session).await.ok())
+    }
+}
+
+/// 保留提取失败的错误，不会导致整个 handler 不匹配
+#[async_trait]
+impl<T, E> FromSessionPart for Result<T, E>
+where
+    T: FromSessionPart + Send,
+    E: From<WalleError> + Send,
+{
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        Ok(T::from_session_part(session).await.map_err(E::from))
+    }
 }
 
 impl FromSessionPart for Segments {
@@ -291,11 +409,8 @@ impl FromSessionPart for Segments {
                 .event
                 .extra
                 .try_get_as_mut::<&mut Vec<Value>>("message")?;
-            let segments = std::mem::replace(segments, vec![]);
-            segments
-                .into_iter()
-                .map(|v| MsgSegment::try_from(v))
-                .collect()
+            let segments = std::mem::take(segments);
+            segments.into_iter().map(MsgSegment::try_from).collect()
         })
     }
 }
@@ -322,6 +437,7 @@ where
         Self: 'async_trait,
     {
         Box::pin(async move {
+            let implt = session.implt().await;
             let event = std::mem::replace(
                 &mut session.event,
                 Event {
@@ -333,8 +449,7 @@ where
                     extra: ValueMap::default(),
                 },
             );
-            let implt = "todo"; //todo
-            Self::parse(event, implt)
+            Self::parse(event, &implt)
         })
     }
 }
@@ -361,4 +476,64 @@ impl<T: FromSessionPart> FromSession for T {
     async fn from_session(mut session: Session) -> WalleResult<Self> {
         Self::from_session_part(&mut session).await
     }
+    fn commands() -> Vec<String> {
+        <T as FromSessionPart>::commands()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ImplCache;
+    use crate::builtin::is;
+    use crate::matcher::mock::{bot_message, session, MockCaller};
+    use crate::{Rule, Signal};
+    use std::sync::Arc;
+    use walle_core::{
+        event::{BaseEvent, Event, ImplLevel, ParseEvent, TryFromEvent},
+        prelude::WalleError,
+        WalleResult,
+    };
+
+    struct Mock;
+
+    impl TryFromEvent<ImplLevel> for Mock {
+        fn try_from_event_mut(_: &mut Event, implt: &str) -> WalleResult<Self> {
+            if implt == "mock" {
+                Ok(Mock)
+            } else {
+                Err(WalleError::DeclareNotMatch("mock", implt.to_owned()))
+            }
+        }
+    }
+
+    type MockEvent = BaseEvent<(), (), (), (), Mock>;
+
+    #[tokio::test]
+    async fn impl_cache() {
+        let caller = Arc::new(MockCaller::default());
+        let impls = ImplCache::default();
+        let of = |bot| {
+            session(bot_message(bot, "hi"), caller.clone(), Default::default())
+                .with_impl_cache(impls.clone())
+        };
+        let s = of("bot");
+        assert_eq!(s.implt().await, "mock");
+        assert!(MockEvent::parse(s.event.clone(), &s.implt().await).is_ok());
+        assert_eq!(is::<MockEvent>().rule(&of("bot")).await, Signal::Matched);
+        assert_eq!(caller.impl_calls(), 1);
+        assert_eq!(of("other").implt().await, "mock");
+        assert_eq!(caller.impl_calls(), 2);
+
+        // 空的实现名称不会被缓存
+        let caller = Arc::new(MockCaller::default().with_impl(""));
+        let impls = ImplCache::default();
+        for _ in 0..2 {
+            let s = session(bot_message("bot", "hi"), caller.clone(), Default::default())
+                .with_impl_cache(impls.clone());
+            assert_eq!(s.implt().await, "");
+            assert_eq!(is::<MockEvent>().rule(&s).await, Signal::NotMatch);
+        }
+        assert_eq!(caller.impl_calls(), 4);
+        assert!(impls.is_empty());
+    }
 }
@@ -0,0 +1,102 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    ops::Deref,
+    sync::Arc,
+};
+
+use walle_core::{prelude::async_trait, WalleError, WalleResult};
+
+use crate::{FromSessionPart, Session};
+
+/// 以类型为键的共享状态，通过 `Matchers::with_state` 注册
+#[derive(Default, Clone)]
+pub struct States(HashMap<TypeId, Arc<dyn Any + Send + Sync>>);
+
+impl States {
+    /// 注册状态，同一类型只保留最后一次注册的值
+    pub fn insert<T: Send + Sync + 'static>(&mut self, state: T) {
+        self.0.insert(TypeId::of::<T>(), Arc::new(state));
+    }
+
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.0
+            .get(&TypeId::of::<T>())
+            .cloned()
+            .and_then(|state| state.downcast().ok())
+    }
+}
+
+impl std::fmt::Debug for States {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("States")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+/// 共享状态 extractor
+///
+/// ```ignore
+/// Matchers::default()
+///     .with_state(Mutex::new(HashMap::<String, u32>::new()))
+///     .add_matcher(matcher(|State(counts): State<Mutex<HashMap<String, u32>>>, s: Session| async move {
+///         ...
+///     }).boxed())
+/// ```
+#[derive(Debug)]
+pub struct State<T>(pub Arc<T>);
+
+impl<T> Clone for State<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Deref for State<T> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[async_trait]
+impl<T: Send + Sync + 'static> FromSessionPart for State<T> {
+    async fn from_session_part(session: &mut Session) -> WalleResult<Self> {
+        session.state().map(Self).ok_or_else(|| {
+            WalleError::Other(format!(
+                "state {} not registered",
+                std::any::type_name::<T>()
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{State, States};
+    use crate::{matcher, MatcherHandler, Matchers};
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn states() {
+        let mut states = States::default();
+        states.insert(AtomicU32::new(1));
+        states.insert("name");
+        assert_eq!(
+            states.get::<AtomicU32>().unwrap().load(Ordering::Relaxed),
+            1
+        );
+        assert_eq!(*states.get::<&str>().unwrap(), "name");
+        assert!(states.get::<String>().is_none());
+
+        let _ = Matchers::default()
+            .with_state(AtomicU32::new(0))
+            .add_matcher(
+                matcher(|counter: State<AtomicU32>, _: crate::Session| async move {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                })
+                .boxed(),
+            );
+    }
+}
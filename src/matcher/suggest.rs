@@ -0,0 +1,152 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use walle_core::{event::Event, util::ValueMapExt};
+
+use crate::{utils::plain_text, MatchersConfig};
+
+/// 注册时收集的命令名，用于未匹配命令时的提示
+#[derive(Debug)]
+pub(crate) struct Suggester {
+    commands: Vec<String>,
+    last: DashMap<String, Instant>,
+    pruned: Mutex<Instant>,
+}
+
+impl Default for Suggester {
+    fn default() -> Self {
+        Self {
+            commands: Vec::default(),
+            last: DashMap::default(),
+            pruned: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+/// 以字符计的编辑距离
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+impl Suggester {
+    pub(crate) fn register(&mut self, commands: Vec<String>) {
+        for command in commands {
+            if !self.commands.contains(&command) {
+                self.commands.push(command);
+            }
+        }
+    }
+
+    /// 与 `word` 最相近的命令名
+    fn closest(&self, word: &str, max_distance: usize) -> Option<&str> {
+        self.commands
+            .iter()
+            .map(|name| (edit_distance(word, name), name))
+            .filter(|(d, name)| *d <= max_distance && *d < name.chars().count())
+            .min_by_key(|(d, _)| *d)
+            .filter(|(d, _)| *d != 0)
+            .map(|(_, name)| name.as_str())
+    }
+
+    /// 消息以非空命令前缀开头且有相近命令时返回提示消息，每个用户受 `interval` 限制
+    pub(crate) fn suggest(&self, config: &MatchersConfig, event: &Event) -> Option<String> {
+        let suggest = config.suggest.as_ref()?;
+        let text = plain_text(event)?;
+        let text = text.trim_start();
+        let mut starts: Vec<&String> = config
+            .command_start_for(event)
+            .iter()
+            .filter(|s| !s.is_empty())
+            .collect();
+        starts.sort_by_key(|s| std::cmp::Reverse(s.len()));
+        let (start, rest) = starts
+            .into_iter()
+            .find_map(|start| Some((start, text.strip_prefix(start.as_str())?)))?;
+        let mut word = rest.split_whitespace().next()?;
+        for sep in config.command_sep.iter().filter(|s| !s.is_empty()) {
+            word = word.split(sep.as_str()).next().unwrap_or(word);
+        }
+        let name = self.closest(word, suggest.max_distance)?;
+
+        let user_id: String = event.extra.get_downcast("user_id").ok()?;
+        let now = Instant::now();
+        let interval = Duration::from_secs(suggest.interval);
+        if self
+            .last
+            .get(&user_id)
+            .is_some_and(|last| now.duration_since(*last) < interval)
+        {
+            return None;
+        }
+        self.last.insert(user_id, now);
+        self.prune(now, interval);
+        Some(
+            suggest
+                .message
+                .replace("{word}", word)
+                .replace("{command}", &format!("{}{}", start, name)),
+        )
+    }
+
+    /// 每隔 `interval` 清理一次已过间隔的用户记录
+    fn prune(&self, now: Instant, interval: Duration) {
+        let mut pruned = self.pruned.lock().unwrap_or_else(|e| e.into_inner());
+        if now.duration_since(*pruned) >= interval {
+            *pruned = now;
+            self.last
+                .retain(|_, last| now.duration_since(*last) < interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{edit_distance, Suggester};
+
+    #[test]
+    fn closest_command() {
+        assert_eq!(edit_distance("roulete", "roulette"), 1);
+        assert_eq!(edit_distance("轮盘", "轮盘赌"), 1);
+        let mut suggester = Suggester::default();
+        suggester.register(vec!["roulette".to_owned(), "轮盘赌".to_owned()]);
+        suggester.register(vec!["shot".to_owned()]);
+        assert_eq!(suggester.closest("rolette", 2), Some("roulette"));
+        assert_eq!(suggester.closest("轮盘", 2), Some("轮盘赌"));
+        assert_eq!(suggester.closest("shot", 2), None);
+        assert_eq!(suggester.closest("ab", 2), None);
+    }
+
+    #[test]
+    fn suggest_message() {
+        use crate::matcher::mock::group_message;
+        use crate::{MatchersConfig, SuggestConfig};
+
+        let mut suggester = Suggester::default();
+        suggester.register(vec!["roulette".to_owned()]);
+        let mut config = MatchersConfig {
+            suggest: Some(SuggestConfig::default()),
+            ..Default::default()
+        };
+        let event = group_message("user", "/rolette");
+        assert_eq!(suggester.suggest(&config, &event), None);
+        config.command_start = vec!["/".to_owned()];
+        assert_eq!(
+            suggester.suggest(&config, &event).as_deref(),
+            Some("未知命令 rolette，你是否想使用 /roulette？")
+        );
+        assert_eq!(suggester.suggest(&config, &event), None);
+    }
+}
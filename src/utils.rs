@@ -1 +1,116 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        OnceLock,
+    },
+};
 
+use time::{macros::format_description, OffsetDateTime, UtcOffset};
+use tracing_subscriber::fmt::{format::Writer, time::FormatTime};
+use walle_core::{
+    event::Event,
+    segment::MsgSegmentRef,
+    util::{Value, ValueMapExt},
+};
+
+/// 拼接 message 中所有 text 消息段的文本
+pub(crate) fn plain_text(event: &Event) -> Option<String> {
+    message_text(event, true)
+}
+
+/// 拼接 message 中所有 text 消息段的文本，不忽略 mention 时将其展开为 `@user_id`
+pub(crate) fn message_text(event: &Event, ignore_mentions: bool) -> Option<String> {
+    let segs = event.extra.try_get_as_ref::<&Vec<Value>>("message").ok()?;
+    let mut text = String::new();
+    for seg in segs {
+        match seg.try_as_ref::<MsgSegmentRef<'_>>() {
+            Ok(MsgSegmentRef::Text { text: t, .. }) => text.push_str(t),
+            Ok(MsgSegmentRef::Mention { user_id, .. }) if !ignore_mentions => {
+                text.push('@');
+                text.push_str(user_id);
+            }
+            _ => {}
+        }
+    }
+    Some(text)
+}
+
+/// 将全角 ASCII 字符与全角空格转换为半角
+pub(crate) fn fold_width(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\u{3000}' => ' ',
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            c => c,
+        })
+        .collect()
+}
+
+/// 常用繁体字与简体字对照，每两个字符为一组
+const TRADITIONAL_SIMPLIFIED: &str = "\
+    萬万與与專专業业東东絲丝兩两嚴严個个豐丰臨临為为麗丽舉举義义樂乐喬乔習习書书買买\
+    亂乱爭争於于虧亏雲云亞亚產产親亲億亿僅仅從从侖仑倉仓儀仪們们價价眾众優优會会傘伞\
+    偉伟傳传傷伤倫伦偽伪體体餘余來来侶侣俠侠倆俩債债傾倾僑侨儲储兒儿黨党蘭兰關关興兴\
+    養养獸兽內内岡冈冊册寫写軍军農农馮冯衝冲決决況况凍冻淨净涼凉減减湊凑凜凛幾几鳳凤\
+    憑凭凱凯擊击鑿凿劃划劉刘則则剛刚創创刪删別别劑剂劍剑勸劝辦办務务動动勵励勁劲勞劳\
+    勢势勳勋區区醫医華华協协單单賣卖盧卢衛卫卻却廠厂廳厅歷历厲厉壓压厭厌縣县參参雙双\
+    發发變变敘叙疊叠號号嘆叹嚇吓嗎吗啟启員员問问啞哑喚唤喪丧圍围園园圖图國国團团聖圣\
+    場场壞坏塊块堅坚壇坛墳坟墜坠壘垒壩坝夠够夢梦頭头奪夺奮奋婦妇媽妈孫孙學学寧宁實实\
+    審审寶宝對对尋寻導导層层屬属歲岁島岛峽峡嶺岭幣币師师帳帐幫帮廣广莊庄慶庆廢废開开\
+    異异彈弹強强歸归當当錄录徹彻徑径後后憶忆懷怀態态戀恋惡恶惱恼悶闷驚惊慣惯憐怜戲戏\
+    戰战戶户撲扑執执擴扩掃扫揚扬擾扰撫抚搶抢護护報报擔担擬拟攏拢揀拣擁拥攔拦擰拧撥拨\
+    擇择掛挂摯挚撈捞損损換换據据擠挤擲掷攜携搖摇數数斷断無无舊旧時时曠旷晝昼顯显晉晋\
+    曬晒曉晓暈晕暫暂術术機机殺杀雜杂權权條条楊杨極极構构樣样標标槍枪樹树橋桥檢检歡欢\
+    歐欧殘残毀毁殼壳氣气漢汉湯汤溝沟沒没滬沪淚泪潔洁灑洒濃浓濤涛潤润漲涨澀涩淵渊漁渔\
+    溫温灣湾濕湿滿满濾滤灘滩瀟潇滅灭燈灯靈灵災灾爐炉點点煉炼爛烂燒烧熱热愛爱爺爷牽牵\
+    犧牺狀状猶犹獨独獲获獵猎貓猫獻献環环現现瑪玛電电畫画暢畅療疗瘋疯盡尽監监盤盘睜睁\
+    礦矿碼码磚砖礎础確确禮礼禍祸離离種种積积稱称穩稳窮穷竊窃競竞筆笔築筑簡简節节範范\
+    類类糧粮緊紧紅红約约級级紀纪納纳純纯紙纸紛纷線线練练組组細细終终紹绍經经結结給给\
+    絡络統统絕绝繼继續续維维綠绿網网緒绪編编緣缘緩缓縮缩總总績绩織织罰罚羅罗聞闻聯联\
+    聰聪聲声職职聽听肅肃腦脑膚肤腳脚臉脸艦舰艱艰藝艺蘇苏蘋苹莖茎薦荐葉叶蕭萧藥药蟲虫\
+    蝦虾螞蚂蠟蜡補补製制複复襲袭見见規规視视覺觉覽览觀观計计訂订認认討讨讓让訓训議议\
+    記记講讲許许論论設设訪访證证評评識识詞词試试詩诗話话該该詳详語语誤误說说請请諸诸\
+    讀读課课誰谁調调談谈謝谢謎谜謊谎謹谨譯译譽誉讚赞豬猪貝贝負负財财貢贡貧贫貨货販贩\
+    貪贪責责貴贵貸贷費费貿贸資资賈贾賊贼賓宾賞赏賠赔賭赌賴赖購购賽赛贏赢趕赶趙赵躍跃\
+    踐践車车軌轨軟软輕轻較较載载輔辅輛辆輝辉輪轮輸输轉转辭辞邊边遼辽達达遷迁過过運运\
+    還还這这進进遠远違违連连遲迟適适選选遺遗郵邮鄉乡醜丑釋释針针釣钓鈕钮鈴铃鉛铅銀银\
+    銅铜鋪铺鋒锋錢钱錯错鍋锅鍵键鎖锁鏡镜鐘钟鐵铁鑰钥長长門门閃闪閉闭閒闲間间閱阅闊阔\
+    隊队陽阳陰阴陣阵階阶際际陸陆險险隨随隱隐難难雞鸡霧雾靜静韓韩響响頁页頂顶項项順顺\
+    須须預预領领頻频題题額额顏颜願愿顧顾風风飛飞飯饭飲饮飽饱餅饼館馆饑饥馬马駕驾驗验\
+    騎骑髮发鬥斗魚鱼鮮鲜鳥鸟鴨鸭鵝鹅鹽盐麥麦黃黄齊齐齒齿龍龙龜龟遊游臺台檯台裡里裏里\
+    麼么麵面鬆松籤签籃篮纜缆聳耸賬账錶表彆别";
+
+/// 将常用繁体字转换为简体字，不在对照表中的字符保持不变
+pub(crate) fn simplify(c: char) -> char {
+    static TABLE: OnceLock<HashMap<char, char>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let mut chars = TRADITIONAL_SIMPLIFIED.chars();
+        std::iter::from_fn(|| Some((chars.next()?, chars.next()?))).collect()
+    });
+    table.get(&c).copied().unwrap_or(c)
+}
+
+/// 日志时间使用的 UTC 偏移秒数，随 `MatchersConfig::utc_offset` 更新
+static LOG_UTC_OFFSET: AtomicI32 = AtomicI32::new(8 * 3600);
+
+pub(crate) fn set_log_utc_offset(offset: UtcOffset) {
+    LOG_UTC_OFFSET.store(offset.whole_seconds(), Ordering::Relaxed);
+}
+
+/// 按配置时区输出的日志时间
+pub(crate) struct LogTimer;
+
+impl FormatTime for LogTimer {
+    fn format_time(&self, w: &mut Writer<'_>) -> std::fmt::Result {
+        let offset = UtcOffset::from_whole_seconds(LOG_UTC_OFFSET.load(Ordering::Relaxed))
+            .unwrap_or(UtcOffset::UTC);
+        let now = OffsetDateTime::now_utc()
+            .to_offset(offset)
+            .format(format_description!(
+                "[year repr:last_two]-[month]-[day] [hour]:[minute]:[second]"
+            ))
+            .map_err(|_| std::fmt::Error)?;
+        w.write_str(&now)
+    }
+}
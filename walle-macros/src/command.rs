@@ -0,0 +1,345 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    spanned::Spanned, Attribute, Data, DeriveInput, Error, Fields, GenericArgument, Lit, Meta,
+    NestedMeta, Path, PathArguments, Result, Type,
+};
+
+/// `#[command(...)]` 与 `#[arg(...)]` 中的 `key = value` 或 `key`
+fn nested_metas(attrs: &[Attribute], ident: &str) -> Result<Vec<Meta>> {
+    let mut metas = vec![];
+    for attr in attrs.iter().filter(|a| a.path.is_ident(ident)) {
+        match attr.parse_meta()? {
+            Meta::List(list) => {
+                for nested in list.nested {
+                    match nested {
+                        NestedMeta::Meta(meta) => metas.push(meta),
+                        NestedMeta::Lit(lit) => {
+                            return Err(Error::new(lit.span(), "expected `key = value`"))
+                        }
+                    }
+                }
+            }
+            meta => return Err(Error::new(meta.span(), "expected a list")),
+        }
+    }
+    Ok(metas)
+}
+
+fn lit_str(lit: &Lit) -> Result<String> {
+    match lit {
+        Lit::Str(s) => Ok(s.value()),
+        lit => Err(Error::new(lit.span(), "expected a string literal")),
+    }
+}
+
+/// 文档注释，多行以空格连接
+fn doc(attrs: &[Attribute]) -> String {
+    attrs
+        .iter()
+        .filter(|a| a.path.is_ident("doc"))
+        .filter_map(|a| match a.parse_meta() {
+            Ok(Meta::NameValue(nv)) => lit_str(&nv.lit).ok(),
+            _ => None,
+        })
+        .map(|s| s.trim().to_owned())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `RollDice` -> `roll-dice`
+fn kebab_case(ident: &str) -> String {
+    let ident = ident.trim_start_matches("r#");
+    let mut s = String::new();
+    for (i, c) in ident.chars().enumerate() {
+        if c.is_uppercase() {
+            if i != 0 {
+                s.push('-');
+            }
+            s.extend(c.to_lowercase());
+        } else if c == '_' {
+            s.push('-');
+        } else {
+            s.push(c);
+        }
+    }
+    s
+}
+
+struct CommandAttrs {
+    name: Option<String>,
+    aliases: Vec<String>,
+    krate: Option<Path>,
+}
+
+fn command_attrs(attrs: &[Attribute]) -> Result<CommandAttrs> {
+    let mut out = CommandAttrs {
+        name: None,
+        aliases: vec![],
+        krate: None,
+    };
+    for meta in nested_metas(attrs, "command")? {
+        match &meta {
+            Meta::NameValue(nv) if nv.path.is_ident("name") => out.name = Some(lit_str(&nv.lit)?),
+            Meta::NameValue(nv) if nv.path.is_ident("alias") => out.aliases.push(lit_str(&nv.lit)?),
+            Meta::NameValue(nv) if nv.path.is_ident("crate") => {
+                out.krate = Some(syn::parse_str(&lit_str(&nv.lit)?)?)
+            }
+            meta => return Err(Error::new(meta.span(), "unknown command attribute")),
+        }
+    }
+    Ok(out)
+}
+
+#[derive(Default)]
+struct ArgAttrs {
+    long: bool,
+    short: Option<char>,
+    default: Option<String>,
+    name: Option<String>,
+}
+
+fn arg_attrs(attrs: &[Attribute]) -> Result<ArgAttrs> {
+    let mut out = ArgAttrs::default();
+    for meta in nested_metas(attrs, "arg")? {
+        match &meta {
+            Meta::Path(path) if path.is_ident("long") => out.long = true,
+            Meta::NameValue(nv) if nv.path.is_ident("short") => match &nv.lit {
+                Lit::Char(c) => out.short = Some(c.value()),
+                lit => return Err(Error::new(lit.span(), "expected a char literal")),
+            },
+            Meta::NameValue(nv) if nv.path.is_ident("default") => {
+                out.default = Some(lit_str(&nv.lit)?)
+            }
+            Meta::NameValue(nv) if nv.path.is_ident("name") => out.name = Some(lit_str(&nv.lit)?),
+            meta => return Err(Error::new(meta.span(), "unknown arg attribute")),
+        }
+    }
+    Ok(out)
+}
+
+enum Shape {
+    Plain,
+    Option,
+    Vec,
+}
+
+/// 根据最外层类型判断参数形式
+fn shape(ty: &Type) -> Shape {
+    let Type::Path(path) = ty else {
+        return Shape::Plain;
+    };
+    let Some(last) = path.path.segments.last() else {
+        return Shape::Plain;
+    };
+    let has_arg = matches!(
+        &last.arguments,
+        PathArguments::AngleBracketed(args)
+            if matches!(args.args.first(), Some(GenericArgument::Type(_)))
+    );
+    match last.ident.to_string().as_str() {
+        "Option" if has_arg => Shape::Option,
+        "Vec" if has_arg => Shape::Vec,
+        _ => Shape::Plain,
+    }
+}
+
+fn is_bool(ty: &Type) -> bool {
+    matches!(ty, Type::Path(path) if path.path.is_ident("bool"))
+}
+
+/// 生成 (ArgSpec 列表, 字段初始化列表)
+fn fields(fields: &Fields, krate: &Path) -> Result<(Vec<TokenStream>, TokenStream)> {
+    let named = match fields {
+        Fields::Named(named) => named,
+        Fields::Unit => return Ok((vec![], quote!())),
+        Fields::Unnamed(f) => {
+            return Err(Error::new(
+                f.span(),
+                "Command only supports named fields or unit",
+            ))
+        }
+    };
+    let mut specs = vec![];
+    let mut inits = vec![];
+    let mut rest_seen = false;
+    for (index, field) in named.named.iter().enumerate() {
+        let ident = field.ident.as_ref().unwrap();
+        let attrs = arg_attrs(&field.attrs)?;
+        let name = attrs.name.unwrap_or_else(|| kebab_case(&ident.to_string()));
+        let help = doc(&field.attrs);
+        let shape = shape(&field.ty);
+        let option = attrs.long || attrs.short.is_some();
+        let (kind, getter) = match shape {
+            Shape::Plain if option && is_bool(&field.ty) => ("Flag", quote!(flag(#index))),
+            Shape::Plain if option => ("Named", quote!(get(#index)?)),
+            Shape::Option if option => ("Named", quote!(get_optional(#index)?)),
+            Shape::Vec if option => ("Named", quote!(get_all(#index)?)),
+            _ if rest_seen => {
+                return Err(Error::new(
+                    field.span(),
+                    "positional argument after a Vec argument",
+                ))
+            }
+            Shape::Plain => ("Positional", quote!(get(#index)?)),
+            Shape::Option => ("Positional", quote!(get_optional(#index)?)),
+            Shape::Vec => {
+                rest_seen = true;
+                ("Rest", quote!(get_all(#index)?))
+            }
+        };
+        let required = matches!(shape, Shape::Plain) && kind != "Flag";
+        let kind = format_ident!("{}", kind);
+        let short = match attrs.short {
+            Some(c) => quote!(Some(#c)),
+            None => quote!(None),
+        };
+        let default = match attrs.default {
+            Some(d) => quote!(Some(#d)),
+            None => quote!(None),
+        };
+        specs.push(quote! {
+            #krate::builtin::ArgSpec {
+                name: #name,
+                help: #help,
+                kind: #krate::builtin::ArgKind::#kind,
+                short: #short,
+                required: #required,
+                default: #default,
+            }
+        });
+        inits.push(quote!(#ident: __parsed.#getter));
+    }
+    Ok((specs, quote!({ #(#inits),* })))
+}
+
+pub(crate) fn derive(input: DeriveInput) -> Result<TokenStream> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(
+            input.generics.span(),
+            "Command does not support generics",
+        ));
+    }
+    let attrs = command_attrs(&input.attrs)?;
+    let krate = attrs.krate.unwrap_or_else(|| syn::parse_quote!(::walle));
+    let ident = &input.ident;
+    let name = attrs.name.unwrap_or_else(|| kebab_case(&ident.to_string()));
+    let aliases = attrs.aliases;
+    let about = doc(&input.attrs);
+
+    let (args, subcommands, parse) = match &input.data {
+        Data::Struct(data) => {
+            let (specs, inits) = fields(&data.fields, &krate)?;
+            let parse = quote! {
+                #[allow(unused_mut, unused_variables)]
+                let mut __parsed = <Self as #krate::builtin::Command>::SPEC.parse(args)?;
+                Ok(Self #inits)
+            };
+            (specs, vec![], parse)
+        }
+        Data::Enum(data) => {
+            let mut subcommands = vec![];
+            let mut arms = vec![];
+            for (index, variant) in data.variants.iter().enumerate() {
+                let v_ident = &variant.ident;
+                let v_attrs = command_attrs(&variant.attrs)?;
+                let v_name = v_attrs
+                    .name
+                    .unwrap_or_else(|| kebab_case(&v_ident.to_string()));
+                let v_aliases = v_attrs.aliases;
+                let v_about = doc(&variant.attrs);
+                match &variant.fields {
+                    Fields::Unnamed(f) if f.unnamed.len() == 1 => {
+                        let ty = &f.unnamed[0].ty;
+                        let about = if v_about.is_empty() {
+                            quote!()
+                        } else {
+                            quote!(about: #v_about,)
+                        };
+                        subcommands.push(quote! {
+                            #krate::builtin::CommandSpec {
+                                name: #v_name,
+                                aliases: &[#(#v_aliases),*],
+                                #about
+                                ..<#ty as #krate::builtin::Command>::SPEC
+                            }
+                        });
+                        arms.push(quote! {
+                            #index => Ok(Self::#v_ident(
+                                <#ty as #krate::builtin::Command>::parse_args(args)?
+                            ))
+                        });
+                    }
+                    fields_ => {
+                        let (specs, inits) = fields(fields_, &krate)?;
+                        subcommands.push(quote! {
+                            #krate::builtin::CommandSpec {
+                                name: #v_name,
+                                aliases: &[#(#v_aliases),*],
+                                about: #v_about,
+                                args: &[#(#specs),*],
+                                subcommands: &[],
+                            }
+                        });
+                        arms.push(quote! {
+                            #index => {
+                                #[allow(unused_mut, unused_variables)]
+                                let mut __parsed = <Self as #krate::builtin::Command>::SPEC
+                                    .subcommands[#index]
+                                    .parse(args)?;
+                                Ok(Self::#v_ident #inits)
+                            }
+                        });
+                    }
+                }
+            }
+            let parse = quote! {
+                match <Self as #krate::builtin::Command>::SPEC.subcommand(args)? {
+                    #(#arms,)*
+                    _ => unreachable!(),
+                }
+            };
+            (vec![], subcommands, parse)
+        }
+        Data::Union(data) => {
+            return Err(Error::new(
+                data.union_token.span,
+                "Command does not support union",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        impl #krate::builtin::Command for #ident {
+            const SPEC: #krate::builtin::CommandSpec = #krate::builtin::CommandSpec {
+                name: #name,
+                aliases: &[#(#aliases),*],
+                about: #about,
+                args: &[#(#args),*],
+                subcommands: &[#(#subcommands),*],
+            };
+            fn parse_args(
+                args: &mut #krate::builtin::CommandArgs,
+            ) -> #krate::walle_core::WalleResult<Self> {
+                #parse
+            }
+        }
+
+        #[#krate::walle_core::prelude::async_trait]
+        impl #krate::FromSessionPart for #ident {
+            async fn from_session_part(
+                session: &mut #krate::Session,
+            ) -> #krate::walle_core::WalleResult<Self> {
+                #krate::builtin::parse_command(session)
+            }
+            fn commands() -> ::std::vec::Vec<::std::string::String> {
+                <Self as #krate::builtin::Command>::SPEC
+                    .names()
+                    .into_iter()
+                    .map(::std::string::String::from)
+                    .collect()
+            }
+        }
+    })
+}
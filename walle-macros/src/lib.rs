@@ -0,0 +1,47 @@
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, AttributeArgs, DeriveInput, ItemImpl};
+
+mod command;
+mod plugin;
+
+/// 为 struct 或 enum 生成 `walle::builtin::Command` 与 `walle::FromSessionPart` 实现
+///
+/// 容器属性：`#[command(name = "...", alias = "...", crate = "...")]`，
+/// 字段属性：`#[arg(long, short = 'c', default = "...", name = "...")]`，
+/// 文档注释将作为用法说明。
+#[proc_macro_derive(Command, attributes(command, arg))]
+pub fn derive_command(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    command::derive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// 将 impl 块中标注 `#[handler]` 的方法注册为 matcher，并生成 `walle::Plugin` 实现
+///
+/// 方法参数依次作为 extractor，最后一个参数需实现 `FromSession`，返回值需实现 `HandlerOutput`。
+/// 可选 `#[plugin(crate = "...")]` 指定 walle 路径。
+#[proc_macro_attribute]
+pub fn plugin(args: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as AttributeArgs);
+    let input = parse_macro_input!(input as ItemImpl);
+    plugin::plugin(args, input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// 标记 handler 方法，需位于 `#[walle::plugin]` impl 块中
+///
+/// 支持 `rule = ...`、`pre_handler = ...`（可重复，按书写顺序由外至内执行）、`awaited`、`silent`
+/// 与 `execution = ...`（`walle::ExecutionPolicy`，同 `BoxedMatcherHandler::execution`）。
+#[proc_macro_attribute]
+pub fn handler(_args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut out: TokenStream = syn::Error::new(
+        proc_macro2::Span::call_site(),
+        "#[handler] must be used inside a #[walle::plugin] impl block",
+    )
+    .into_compile_error()
+    .into();
+    out.extend(input);
+    out
+}
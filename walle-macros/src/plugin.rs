@@ -0,0 +1,251 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    spanned::Spanned,
+    AttributeArgs, Error, Expr, FnArg, Ident, ImplItem, ImplItemMethod, ItemImpl, Lit, Meta,
+    NestedMeta, Path, Result, Token, Type,
+};
+
+/// `#[handler(...)]` 中的单个参数
+enum HandlerArg {
+    Rule(Expr),
+    PreHandler(Expr),
+    Execution(Expr),
+    Awaited,
+    Silent,
+}
+
+impl Parse for HandlerArg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let ident: Ident = input.parse()?;
+        match ident.to_string().as_str() {
+            "awaited" => Ok(Self::Awaited),
+            "silent" => Ok(Self::Silent),
+            "rule" => {
+                input.parse::<Token![=]>()?;
+                Ok(Self::Rule(input.parse()?))
+            }
+            "pre_handler" => {
+                input.parse::<Token![=]>()?;
+                Ok(Self::PreHandler(input.parse()?))
+            }
+            "execution" => {
+                input.parse::<Token![=]>()?;
+                Ok(Self::Execution(input.parse()?))
+            }
+            _ => Err(Error::new(ident.span(), "unknown handler attribute")),
+        }
+    }
+}
+
+fn is_handler_attr(path: &Path) -> bool {
+    path.segments.last().is_some_and(|s| s.ident == "handler")
+}
+
+/// 取出并移除方法上的 `#[handler]`，非 handler 方法返回 None
+fn take_handler_args(method: &mut ImplItemMethod) -> Result<Option<Vec<HandlerArg>>> {
+    let Some(index) = method.attrs.iter().position(|a| is_handler_attr(&a.path)) else {
+        return Ok(None);
+    };
+    let attr = method.attrs.remove(index);
+    if attr.tokens.is_empty() {
+        return Ok(Some(vec![]));
+    }
+    let args = attr.parse_args_with(Punctuated::<HandlerArg, Token![,]>::parse_terminated)?;
+    Ok(Some(args.into_iter().collect()))
+}
+
+fn plugin_crate(args: AttributeArgs) -> Result<Path> {
+    let mut krate = syn::parse_quote!(::walle);
+    for arg in args {
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("crate") => match &nv.lit {
+                Lit::Str(s) => krate = s.parse()?,
+                lit => return Err(Error::new(lit.span(), "expected a string literal")),
+            },
+            arg => return Err(Error::new(arg.span(), "unknown plugin attribute")),
+        }
+    }
+    Ok(krate)
+}
+
+/// 为单个 handler 方法生成 MatcherHandler 与注册表达式
+fn handler(
+    method: &ImplItemMethod,
+    args: Vec<HandlerArg>,
+    self_ty: &Ident,
+    krate: &Path,
+) -> Result<(TokenStream, TokenStream)> {
+    let sig = &method.sig;
+    if sig.asyncness.is_none() {
+        return Err(Error::new(sig.fn_token.span, "handler must be async"));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(Error::new(
+            sig.generics.span(),
+            "handler does not support generics",
+        ));
+    }
+    let mut inputs = sig.inputs.iter();
+    match inputs.next() {
+        Some(FnArg::Receiver(r)) if r.reference.is_some() && r.mutability.is_none() => {}
+        Some(FnArg::Typed(t)) if matches!(&*t.pat, syn::Pat::Ident(p) if p.ident == "self") => {}
+        _ => {
+            return Err(Error::new(
+                sig.inputs.span(),
+                "handler requires `&self` or `self: &Arc<Self>`",
+            ))
+        }
+    }
+    let mut tys = vec![];
+    for input in inputs {
+        let FnArg::Typed(t) = input else {
+            unreachable!()
+        };
+        if let Type::Reference(r) = &*t.ty {
+            return Err(Error::new(r.span(), "handler arguments must be owned"));
+        }
+        tys.push(&*t.ty);
+    }
+
+    let name = &sig.ident;
+    let wrapper = format_ident!("__{}_{}", self_ty, name);
+    let idents: Vec<Ident> = (0..tys.len())
+        .map(|i| format_ident!("__arg{}", i))
+        .collect();
+    let mut extracts = vec![];
+    for (i, (ident, ty)) in idents.iter().zip(&tys).enumerate() {
+        extracts.push(if i + 1 == tys.len() {
+            quote! {
+                let __output_session = session.clone();
+                let #ident = match <#ty as #krate::FromSession>::from_session(session).await {
+                    Ok(e) => e,
+                    Err(e) => return #krate::extract_failed(&__output_session, e, __options),
+                };
+            }
+        } else {
+            quote! {
+                let #ident =
+                    match <#ty as #krate::FromSessionPart>::from_session_part(&mut session).await {
+                        Ok(e) => e,
+                        Err(e) => return #krate::extract_failed(&session, e, __options),
+                    };
+            }
+        });
+    }
+    if tys.is_empty() {
+        extracts.push(quote!(let __output_session = session;));
+    }
+
+    let awaited = args.iter().any(|a| matches!(a, HandlerArg::Awaited));
+    let silent = args.iter().any(|a| matches!(a, HandlerArg::Silent));
+    let execution = match args.iter().rev().find_map(|a| match a {
+        HandlerArg::Execution(policy) => Some(policy),
+        _ => None,
+    }) {
+        Some(policy) => quote!(#krate::Execution::new(#policy)),
+        None => quote!(::std::default::Default::default()),
+    };
+    let layers = args.iter().rev().filter_map(|arg| match arg {
+        HandlerArg::Rule(rule) => Some(quote!(let h = #krate::Rule::layer(#rule, h);)),
+        HandlerArg::PreHandler(pre) => Some(quote!(let h = #krate::PreHandler::layer(#pre, h);)),
+        HandlerArg::Execution(_) | HandlerArg::Awaited | HandlerArg::Silent => None,
+    });
+
+    let item = quote! {
+        #[doc(hidden)]
+        #[allow(non_camel_case_types)]
+        struct #wrapper(::std::sync::Arc<#self_ty>, #krate::HandlerOptions);
+
+        #[#krate::walle_core::prelude::async_trait]
+        impl #krate::MatcherHandler for #wrapper {
+            #[allow(unused_mut)]
+            async fn handle(&self, mut session: #krate::Session) -> #krate::Signal {
+                let __options = &self.1;
+                #(#extracts)*
+                let __this = self.0.clone();
+                #krate::run_handler(
+                    async move { __this.#name(#(#idents),*).await },
+                    __output_session,
+                    __options,
+                )
+                .await
+            }
+            fn commands(&self) -> ::std::vec::Vec<::std::string::String> {
+                let mut commands = ::std::vec::Vec::new();
+                #(commands.extend(<#tys as #krate::FromSession>::commands());)*
+                commands
+            }
+        }
+    };
+    let register = quote! {
+        {
+            let h = #wrapper(
+                self.clone(),
+                #krate::HandlerOptions {
+                    awaited: #awaited,
+                    silent: #silent,
+                    execution: #execution,
+                },
+            );
+            #(#layers)*
+            ::std::boxed::Box::new(h) as #krate::Matcher
+        }
+    };
+    Ok((item, register))
+}
+
+pub(crate) fn plugin(args: AttributeArgs, mut input: ItemImpl) -> Result<TokenStream> {
+    let krate = plugin_crate(args)?;
+    if let Some((_, path, _)) = &input.trait_ {
+        return Err(Error::new(
+            path.span(),
+            "plugin must be used on an inherent impl block",
+        ));
+    }
+    if !input.generics.params.is_empty() {
+        return Err(Error::new(
+            input.generics.span(),
+            "plugin does not support generics",
+        ));
+    }
+    let self_ty = match &*input.self_ty {
+        Type::Path(p) if p.qself.is_none() && p.path.get_ident().is_some() => {
+            p.path.get_ident().unwrap().clone()
+        }
+        ty => return Err(Error::new(ty.span(), "plugin requires a plain struct name")),
+    };
+
+    let mut items = vec![];
+    let mut registers = vec![];
+    for item in input.items.iter_mut() {
+        let ImplItem::Method(method) = item else {
+            continue;
+        };
+        if let Some(args) = take_handler_args(method)? {
+            let (item, register) = handler(method, args, &self_ty, &krate)?;
+            items.push(item);
+            registers.push(register);
+        }
+    }
+    if registers.is_empty() {
+        return Err(Error::new(
+            input.impl_token.span,
+            "plugin requires at least one #[handler] method",
+        ));
+    }
+
+    Ok(quote! {
+        #input
+
+        #(#items)*
+
+        impl #krate::Plugin for #self_ty {
+            fn matchers(self: ::std::sync::Arc<Self>) -> ::std::vec::Vec<#krate::Matcher> {
+                ::std::vec![#(#registers),*]
+            }
+        }
+    })
+}